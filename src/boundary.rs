@@ -0,0 +1,103 @@
+//! Removes bodies that leave the play area for good.
+//!
+//! Anything pulled by gravity that is beyond the [`WorldBoundary`] and on an escape trajectory away from the body
+//! it orbits would keep flying forever, so it is despawned and reported with a [`LostToSpaceEvent`].
+
+use crate::dev_tools::is_debug_enabled;
+use crate::physics::calc_gravity::Attractee;
+use crate::physics::orbital_elements::OrbitalElements;
+use crate::physics::velocity::Velocity;
+use crate::sun_system::Satellite;
+use crate::sun_system::asteroids::AsteroidSwarm;
+use crate::{AppSystems, GameplaySystem};
+use bevy::color::palettes::basic::GRAY;
+use bevy::prelude::*;
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<WorldBoundary>();
+    app.init_resource::<LostToSpace>();
+    app.add_systems(
+        FixedUpdate,
+        remove_escaped_bodies
+            .in_set(AppSystems::Update)
+            .in_set(GameplaySystem),
+    );
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        draw_world_boundary
+            .run_if(is_debug_enabled)
+            .in_set(GameplaySystem),
+    );
+}
+
+/// The edge of the play area around the sun
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct WorldBoundary {
+    /// Distance from the sun beyond which escaping bodies are removed, must be larger than where asteroids appear
+    pub radius: f32,
+}
+
+impl Default for WorldBoundary {
+    fn default() -> Self {
+        Self { radius: 600.0 }
+    }
+}
+
+/// How many bodies were lost to space during the match
+#[derive(Resource, Debug, Default, Copy, Clone, PartialEq)]
+pub struct LostToSpace {
+    pub collectors: u32,
+    pub swarms: u32,
+}
+
+/// Triggered right before a body that escaped the play area is despawned
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct LostToSpaceEvent {
+    pub entity: Entity,
+}
+
+type EscapingBodyData = (
+    Entity,
+    &'static Transform,
+    &'static Velocity,
+    &'static OrbitalElements,
+    Has<Satellite>,
+    Has<AsteroidSwarm>,
+);
+
+fn remove_escaped_bodies(
+    mut commands: Commands,
+    boundary: Res<WorldBoundary>,
+    mut lost: ResMut<LostToSpace>,
+    references: Query<&Transform>,
+    query: Query<EscapingBodyData, With<Attractee>>,
+) {
+    query.iter().for_each(|(i_entity, i_trans, i_velocity, i_elements, i_collector, i_swarm)| {
+        let position = i_trans.translation.xy();
+        if i_elements.bound || position.length() < boundary.radius {
+            return;
+        }
+        // a body on its way in is about to enter the play area, e.g. an asteroid swarm that just appeared
+        let reference = i_elements
+            .reference
+            .and_then(|reference| references.get(reference).ok())
+            .map_or(Vec2::ZERO, |reference| reference.translation.xy());
+        if i_velocity.0.dot(position - reference) <= 0.0 {
+            return;
+        }
+
+        info!("{i_entity} was lost to space");
+        lost.collectors += i_collector as u32;
+        lost.swarms += i_swarm as u32;
+        // observers get to see the body before it is gone
+        commands.trigger(LostToSpaceEvent { entity: i_entity });
+        commands.entity(i_entity).despawn();
+    });
+}
+
+fn draw_world_boundary(mut gizmos: Gizmos, boundary: Res<WorldBoundary>) {
+    gizmos.circle_2d(Isometry2d::IDENTITY, boundary.radius, GRAY);
+}
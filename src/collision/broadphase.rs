@@ -0,0 +1,172 @@
+//! A uniform grid which narrows down which hitboxes could possibly touch
+//! so that not every pair of hitboxes needs to be swept against each other.
+
+use crate::collision::{HitBox, PreviousPosition};
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+#[derive(Resource, Debug)]
+pub struct CollisionGrid {
+    /// Edge length of a single grid cell in world units
+    pub cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+}
+
+impl Default for CollisionGrid {
+    fn default() -> Self {
+        Self {
+            cell_size: 32.0,
+            cells: HashMap::default(),
+        }
+    }
+}
+
+impl CollisionGrid {
+    pub fn clear(&mut self) {
+        // keep the allocated cells around since most of them will be reused in the next tick
+        self.cells.values_mut().for_each(Vec::clear);
+    }
+
+    /// Insert an entity into every cell touched by the axis aligned box between `min` and `max`
+    pub fn insert(&mut self, entity: Entity, min: Vec2, max: Vec2) {
+        for cell in self.cells_between(min, max) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+    }
+
+    /// Entities in any of the cells touched by the axis aligned box between `min` and `max`.
+    /// Entities in several of those cells are returned once for each of them.
+    pub fn entities_near(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        self.cells_between(min, max)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    fn cells_between(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = IVec2> + use<> {
+        let min_cell = (min / self.cell_size).floor().as_ivec2();
+        let max_cell = (max / self.cell_size).floor().as_ivec2();
+        (min_cell.x..=max_cell.x).flat_map(move |x| (min_cell.y..=max_cell.y).map(move |y| IVec2::new(x, y)))
+    }
+
+    /// All pairs of entities which share at least one cell.
+    /// Every pair is only returned once, ordered so that the first entity is the smaller one.
+    pub fn candidate_pairs(&self) -> HashSet<(Entity, Entity)> {
+        let mut pairs = HashSet::default();
+        for cell in self.cells.values() {
+            for (i, a) in cell.iter().enumerate() {
+                for b in &cell[i + 1..] {
+                    if a != b {
+                        pairs.insert((*a.min(b), *a.max(b)));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+}
+
+pub(super) fn rebuild_collision_grid(
+    mut grid: ResMut<CollisionGrid>,
+    query: Query<(Entity, &GlobalTransform, &PreviousPosition, &HitBox)>,
+) {
+    grid.clear();
+    query.iter().for_each(|(i_entity, i_trans, i_previous, i_hitbox)| {
+        // cover the whole path the hitbox swept along since the last check
+        let pos = i_trans.translation().xy();
+        let previous = i_previous.0.unwrap_or(pos);
+        grid.insert(
+            i_entity,
+            pos.min(previous) - Vec2::splat(i_hitbox.shape.bounding_radius()),
+            pos.max(previous) + Vec2::splat(i_hitbox.shape.bounding_radius()),
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(index: u32) -> Entity {
+        Entity::from_raw_u32(index).unwrap()
+    }
+
+    /// A pair in the order the grid returns it
+    fn pair(a: u32, b: u32) -> (Entity, Entity) {
+        (entity(a).min(entity(b)), entity(a).max(entity(b)))
+    }
+
+    /// A grid with 10 units wide cells and a circle of `radius` around each position
+    fn grid(bodies: &[(Vec2, f32)]) -> CollisionGrid {
+        let mut grid = CollisionGrid {
+            cell_size: 10.0,
+            ..default()
+        };
+        for (index, (position, radius)) in bodies.iter().enumerate() {
+            grid.insert(entity(index as u32), position - Vec2::splat(*radius), position + Vec2::splat(*radius));
+        }
+        grid
+    }
+
+    #[test]
+    fn pairs_sharing_several_cells_are_returned_once() {
+        // both bodies cover the same four cells around the origin
+        let grid = grid(&[(Vec2::ZERO, 2.0), (Vec2::new(1.0, 1.0), 2.0)]);
+
+        assert_eq!(grid.candidate_pairs(), HashSet::from_iter([pair(0, 1)]));
+    }
+
+    #[test]
+    fn bodies_straddling_a_cell_border_are_paired_with_both_sides() {
+        let grid = grid(&[(Vec2::new(9.5, 5.0), 1.0), (Vec2::new(5.0, 5.0), 1.0), (Vec2::new(15.0, 5.0), 1.0)]);
+
+        assert_eq!(
+            grid.candidate_pairs(),
+            HashSet::from_iter([pair(0, 1), pair(0, 2)])
+        );
+    }
+
+    #[test]
+    fn bodies_larger_than_a_cell_are_paired_with_everything_they_cover() {
+        let grid = grid(&[
+            (Vec2::ZERO, 25.0),
+            (Vec2::new(-22.0, 0.0), 1.0),
+            (Vec2::new(0.0, 22.0), 1.0),
+            (Vec2::new(18.0, -18.0), 1.0),
+        ]);
+
+        assert_eq!(
+            grid.candidate_pairs(),
+            HashSet::from_iter([pair(0, 1), pair(0, 2), pair(0, 3)])
+        );
+    }
+
+    #[test]
+    fn far_apart_bodies_are_not_paired() {
+        let grid = grid(&[(Vec2::ZERO, 2.0), (Vec2::new(50.0, 0.0), 2.0), (Vec2::new(0.0, -50.0), 2.0)]);
+
+        assert!(grid.candidate_pairs().is_empty());
+    }
+
+    #[test]
+    fn entities_near_a_box_are_found_without_inserting_it() {
+        let grid = grid(&[(Vec2::ZERO, 2.0), (Vec2::new(25.0, 5.0), 1.0), (Vec2::new(80.0, 0.0), 1.0)]);
+
+        let mut near = grid.entities_near(Vec2::new(1.0, -1.0), Vec2::new(21.0, 3.0)).collect::<Vec<_>>();
+        near.sort();
+        near.dedup();
+        let mut expected = vec![entity(0), entity(1)];
+        expected.sort();
+        assert_eq!(near, expected);
+        assert_eq!(grid.entities_near(Vec2::new(50.0, 50.0), Vec2::new(55.0, 55.0)).count(), 0);
+    }
+
+    #[test]
+    fn cleared_grid_forgets_its_bodies() {
+        let mut grid = grid(&[(Vec2::ZERO, 2.0), (Vec2::new(1.0, 1.0), 2.0)]);
+        grid.clear();
+        grid.insert(entity(2), Vec2::splat(-1.0), Vec2::splat(1.0));
+
+        assert!(grid.candidate_pairs().is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+//! Predicts collisions between collectors and asteroid swarms before they happen.
+//!
+//! Every few seconds all collectors and swarms are propagated forward and the collectors are swept against
+//! whatever comes close to them, the first contacts within the horizon are kept in [`Conjunctions`] for the HUD
+//! and the projections.
+
+use crate::collision::HitBox;
+use crate::collision::broadphase::CollisionGrid;
+use crate::collision::shape::{HitBoxShape, shape_isometry, sweep_shapes};
+use crate::physics::calc_gravity::{
+    Attractee, Attractor, GravityModel, GravitySource, GravitySourceData, collect_gravity_sources, dominant_body,
+};
+use crate::physics::directional_forces::Mass;
+use crate::physics::integrator::Integrator;
+use crate::physics::velocity::Velocity;
+use crate::sun_system::Satellite;
+use crate::sun_system::navigation_instruments::NavigationInstruments;
+use bevy::color::palettes::basic::RED;
+use bevy::diagnostic::{DiagnosticPath, Diagnostics};
+use bevy::ecs::system::SystemParam;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::platform::time::Instant;
+use bevy::prelude::*;
+
+/// Milliseconds spent on a prediction
+pub const CONJUNCTION_COST: DiagnosticPath = DiagnosticPath::const_new("conjunctions/cost");
+/// Number of pairs swept against each other in a prediction
+pub const CONJUNCTION_SWEEPS: DiagnosticPath = DiagnosticPath::const_new("conjunctions/sweeps");
+
+/// How far and how finely conjunctions are predicted
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct ConjunctionConfig {
+    /// How many seconds into the future conjunctions are searched for
+    pub horizon: f32,
+    /// Length of a single propagation step in seconds
+    pub step: f32,
+    /// Seconds between two predictions
+    pub interval: f32,
+    /// For how many collectors conjunctions are searched for, the selected collector goes first.
+    /// The others are only checked against those.
+    pub max_collectors: usize,
+}
+
+impl Default for ConjunctionConfig {
+    fn default() -> Self {
+        Self {
+            horizon: 30.0,
+            step: 0.25,
+            interval: 0.5,
+            max_collectors: 8,
+        }
+    }
+}
+
+/// An upcoming collision of two bodies
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Conjunction {
+    pub a: Entity,
+    pub b: Entity,
+    /// Seconds from the last prediction until the hitboxes touch
+    pub in_seconds: f32,
+    /// Where the bodies touch, relative to where the orbited body is now like the orbit projections
+    pub position: Vec2,
+}
+
+/// The conjunctions found by the last prediction, soonest first
+#[derive(Resource, Debug, Default)]
+pub struct Conjunctions(pub Vec<Conjunction>);
+
+/// A body while it is propagated
+struct PredictedBody {
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    /// Bodies are assumed to keep their current rotation
+    rotation: Quat,
+    shape: HitBoxShape,
+    /// `None` for bodies that are not pulled by gravity and just drift along
+    mass: Option<Mass>,
+    /// Whether conjunctions are searched for this body
+    collector: bool,
+    /// Whether it crashed into an attractor and won't meet anything anymore
+    crashed: bool,
+}
+
+type PredictableBodyData = (
+    Entity,
+    &'static GlobalTransform,
+    &'static HitBox,
+    Has<Satellite>,
+    Option<&'static ChildOf>,
+);
+
+/// The bodies which are propagated and how they move
+#[derive(SystemParam)]
+pub(super) struct PredictedWorld<'w, 's> {
+    integrator: Res<'w, Integrator>,
+    gravity: Res<'w, GravityModel>,
+    attractor: Query<'w, 's, GravitySourceData, With<Attractor>>,
+    obstacles: Query<'w, 's, &'static HitBox, With<Attractor>>,
+    bodies: Query<'w, 's, PredictableBodyData, Without<Attractor>>,
+    movers: Query<'w, 's, (&'static Velocity, Option<&'static Mass>, Has<Attractee>)>,
+    selected: Query<'w, 's, (), With<NavigationInstruments>>,
+}
+
+/// What is kept from one prediction to the next
+#[derive(Debug, Default)]
+pub(super) struct PredictionState {
+    /// Seconds since the last prediction
+    since_last: f32,
+    /// Reused so that its cells don't have to be allocated again
+    grid: CollisionGrid,
+}
+
+pub(super) fn predict_conjunctions(
+    config: Res<ConjunctionConfig>,
+    time: Res<Time>,
+    mut state: Local<PredictionState>,
+    mut conjunctions: ResMut<Conjunctions>,
+    mut diagnostics: Diagnostics,
+    world: PredictedWorld,
+) {
+    state.since_last += time.delta_secs();
+    if state.since_last < config.interval {
+        return;
+    }
+    state.since_last = 0.0;
+    let started = Instant::now();
+    let PredictedWorld {
+        integrator,
+        gravity,
+        attractor,
+        obstacles,
+        bodies,
+        movers,
+        selected,
+    } = world;
+    let grid = &mut state.grid;
+
+    let sources = collect_gravity_sources(attractor.iter());
+    let mut projected_sources = sources.clone();
+    let mut predicted = bodies
+        .iter()
+        .filter_map(|(entity, transform, hitbox, collector, parent)| {
+            // asteroids of a swarm move along with it
+            let (velocity, mass, attractee) = movers
+                .get(entity)
+                .ok()
+                .or_else(|| movers.get(parent?.parent()).ok())?;
+            Some(PredictedBody {
+                entity,
+                position: transform.translation().xy(),
+                velocity: velocity.0,
+                rotation: transform.rotation(),
+                shape: hitbox.shape.clone(),
+                mass: mass.copied().filter(|_| attractee),
+                collector,
+                crashed: false,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // beyond the budget collectors are treated like any other body
+    let mut collectors = predicted
+        .iter()
+        .enumerate()
+        .filter(|(_, body)| body.collector)
+        .map(|(index, body)| (index, selected.contains(body.entity), body.entity))
+        .collect::<Vec<_>>();
+    collectors.sort_by(|(_, selected_a, entity_a), (_, selected_b, entity_b)| {
+        selected_b.cmp(selected_a).then(entity_a.cmp(entity_b))
+    });
+    for (index, ..) in collectors.iter().skip(config.max_collectors) {
+        predicted[*index].collector = false;
+    }
+    let indices = predicted
+        .iter()
+        .enumerate()
+        .map(|(index, body)| (body.entity, index))
+        .collect::<HashMap<_, _>>();
+
+    let mut found = Vec::<Conjunction>::new();
+    let mut sweeps = 0;
+    let steps = (config.horizon / config.step).ceil() as usize;
+    for step in 0..steps {
+        let start = predicted.iter().map(|body| body.position).collect::<Vec<_>>();
+        for body in predicted.iter_mut().filter(|body| !body.crashed) {
+            match body.mass {
+                Some(mass) => {
+                    (body.position, body.velocity) = integrator.step(body.position, body.velocity, config.step, |position| {
+                        gravity.force(&projected_sources, Some(body.entity), &mass, position) / mass.0
+                    });
+                }
+                None => body.position += body.velocity * config.step,
+            }
+        }
+        integrator.step_sources(&gravity, &mut projected_sources, config.step);
+
+        // only collectors are of interest, swarms passing through each other don't matter
+        let bounds = |index: usize| {
+            let radius = Vec2::splat(predicted[index].shape.bounding_radius());
+            (start[index].min(predicted[index].position) - radius, start[index].max(predicted[index].position) + radius)
+        };
+        grid.clear();
+        for (index, body) in predicted.iter().enumerate().filter(|(_, body)| body.collector && !body.crashed) {
+            let (min, max) = bounds(index);
+            grid.insert(body.entity, min, max);
+        }
+        let mut pairs = HashSet::<(usize, usize)>::default();
+        for (index, _) in predicted.iter().enumerate().filter(|(_, body)| !body.crashed) {
+            let (min, max) = bounds(index);
+            pairs.extend(
+                grid.entities_near(min, max)
+                    .map(|entity| indices[&entity])
+                    .filter(|other| *other != index)
+                    .map(|other| (index.min(other), index.max(other))),
+            );
+        }
+        let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+        pairs.sort();
+
+        for (i, j) in pairs {
+            let (a, b) = (&predicted[i], &predicted[j]);
+            if found.iter().any(|conjunction| conjunction.a == a.entity && conjunction.b == b.entity) {
+                continue;
+            }
+            sweeps += 1;
+            let Some(toi) = sweep_shapes(
+                &a.shape,
+                shape_isometry(start[i], a.rotation),
+                shape_isometry(a.position, a.rotation),
+                &b.shape,
+                shape_isometry(start[j], b.rotation),
+                shape_isometry(b.position, b.rotation),
+            ) else {
+                continue;
+            };
+            let position = start[i].lerp(a.position, toi);
+            found.push(Conjunction {
+                a: a.entity,
+                b: b.entity,
+                in_seconds: (step as f32 + toi) * config.step,
+                position: position + drawing_offset(&sources, &projected_sources, a.entity, position),
+            });
+        }
+
+        // bodies that fly into a planet or the sun are gone
+        for body in predicted.iter_mut() {
+            body.crashed |= projected_sources.iter().any(|source| {
+                obstacles
+                    .get(source.entity)
+                    .is_ok_and(|hitbox| {
+                        let isometry = shape_isometry(body.position, body.rotation);
+                        hitbox.shape.distance(Isometry2d::from_translation(source.position), &body.shape, isometry) < 0.0
+                    })
+            });
+        }
+    }
+
+    found.sort_by(|a, b| a.in_seconds.total_cmp(&b.in_seconds));
+    conjunctions.0 = found;
+
+    diagnostics.add_measurement(&CONJUNCTION_COST, || started.elapsed().as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&CONJUNCTION_SWEEPS, || sweeps as f64);
+}
+
+/// Shift a predicted position the same way the orbit projections are shifted,
+/// i.e. by how far the body that is orbited there moves until then
+fn drawing_offset(sources: &[GravitySource], projected_sources: &[GravitySource], entity: Entity, position: Vec2) -> Vec2 {
+    let Some(reference) = dominant_body(projected_sources, Some(entity), position) else {
+        return Vec2::ZERO;
+    };
+    sources
+        .iter()
+        .find(|source| source.entity == reference.entity)
+        .map_or(Vec2::ZERO, |now| now.position - reference.position)
+}
+
+/// Mark where the predicted collisions happen
+pub(super) fn draw_conjunctions(mut gizmos: Gizmos, conjunctions: Res<Conjunctions>) {
+    for conjunction in &conjunctions.0 {
+        let isometry = Isometry2d::from_translation(conjunction.position);
+        gizmos.circle_2d(isometry, 5.0, RED);
+        gizmos.cross_2d(isometry, 3.0, RED);
+    }
+}
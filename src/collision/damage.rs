@@ -0,0 +1,111 @@
+//! Hit points of collectors and how much damage impacts do.
+//!
+//! The faster and heavier whatever hits a collector is, the more damage it takes. A damaged collector
+//! collects less energy and breaks apart once its health drops below the destroy threshold.
+
+use crate::collision::layers::{CollisionLayer, CollisionMask};
+use crate::sun_system::Level;
+use bevy::color::palettes::css::{ORANGE_RED, WHITE, YELLOW};
+use bevy::prelude::*;
+
+/// How tough collectors are and how hard impacts hit
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct DamageConfig {
+    /// Damage per unit of relative speed when hit by something of the same mass
+    pub damage_per_speed: f32,
+    /// Collectors break apart once less than this fraction of their health is left
+    pub destroy_threshold: f32,
+    /// Bodies without health on these layers are destroyed by a damaging contact, on the others they are unharmed
+    pub destroyed_without_health: CollisionMask,
+}
+
+impl Default for DamageConfig {
+    fn default() -> Self {
+        Self {
+            damage_per_speed: 10.0,
+            destroy_threshold: 0.1,
+            // asteroids break into fragments and debris is ground up by whatever hits it
+            destroyed_without_health: CollisionMask::NONE
+                .with(CollisionLayer::Collector)
+                .with(CollisionLayer::Asteroid)
+                .with(CollisionLayer::Debris),
+        }
+    }
+}
+
+impl DamageConfig {
+    /// Damage a body of `mass` takes when hit by a body of `other_mass` at `relative_speed`.
+    /// Scales with the share of the momentum it has to absorb, so light debris does little and asteroids a lot.
+    pub fn impact_damage(&self, relative_speed: f32, mass: f32, other_mass: f32) -> f32 {
+        let transfer = 2.0 * other_mass / (mass + other_mass).max(f32::EPSILON);
+        self.damage_per_speed * relative_speed * transfer
+    }
+}
+
+/// Health of a collector, better collectors can take more
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::for_level(&Level { level: 1.0 })
+    }
+}
+
+/// Health of a collector of level 1
+const HEALTH_PER_LEVEL: f32 = 100.0;
+
+impl Health {
+    pub fn for_level(level: &Level) -> Self {
+        let max = HEALTH_PER_LEVEL * level.level.max(1.0);
+        Self { current: max, max }
+    }
+
+    /// How much health is left, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        (self.current / self.max).clamp(0.0, 1.0)
+    }
+}
+
+/// How damaged a collector looks
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DamageState {
+    Intact,
+    Damaged,
+    Critical,
+}
+
+impl DamageState {
+    pub fn of(health: &Health) -> Self {
+        match health.fraction() {
+            fraction if fraction > 0.66 => DamageState::Intact,
+            fraction if fraction > 0.33 => DamageState::Damaged,
+            _ => DamageState::Critical,
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self {
+            DamageState::Intact => WHITE.into(),
+            DamageState::Damaged => YELLOW.into(),
+            DamageState::Critical => ORANGE_RED.into(),
+        }
+    }
+}
+
+/// Collectors whose health or sprite changed since the last check
+type DamageOrSpriteChanged = Or<(Changed<Health>, Changed<Sprite>)>;
+
+/// Tint collectors by how damaged they are
+pub(super) fn show_damage(mut query: Query<(&Health, &mut Sprite), DamageOrSpriteChanged>) {
+    query.iter_mut().for_each(|(i_health, mut i_sprite)| {
+        let tint = DamageState::of(i_health).tint();
+        // only touch the sprite if needed so that the change detection above settles
+        if i_sprite.color != tint {
+            i_sprite.color = tint;
+        }
+    });
+}
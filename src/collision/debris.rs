@@ -0,0 +1,119 @@
+//! Debris that collectors shed when they are hit.
+//!
+//! The pieces keep the velocity of the collector they came from, so they stay on roughly the same orbit
+//! and threaten every other collector in it until they decay. A collector hit by debris sheds debris itself,
+//! which is how crowded orbits turn into a cascade.
+
+use crate::RandomSource;
+use crate::collision::HitBox;
+use crate::collision::layers::{CollisionLayer, CollisionMask};
+use crate::collision::shape::HitBoxShape;
+use crate::physics::calc_gravity::Attractee;
+use crate::physics::directional_forces::Mass;
+use crate::physics::velocity::Velocity;
+use bevy::color::palettes::basic::GRAY;
+use bevy::prelude::*;
+use rand::Rng;
+use std::ops::Range;
+
+/// How much debris collisions leave behind and for how long
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct DebrisConfig {
+    /// Pieces shed by a collector that is destroyed, one that only takes damage sheds half as many
+    pub pieces: usize,
+    /// Fastest speed at which pieces drift away from the collector they came from
+    pub spread_speed: f32,
+    /// A range of how many seconds a piece stays in orbit before it decays
+    pub lifetime: Range<f32>,
+    /// Seconds during which a new piece can't hit anything, so that it doesn't hit the collector it came from
+    pub arming_time: f32,
+    pub radius: f32,
+}
+
+impl Default for DebrisConfig {
+    fn default() -> Self {
+        Self {
+            pieces: 6,
+            spread_speed: 3.0,
+            lifetime: 60.0..120.0,
+            arming_time: 2.0,
+            radius: 1.0,
+        }
+    }
+}
+
+/// A piece of debris in orbit
+#[derive(Component, Debug, Clone)]
+pub struct Debris {
+    /// Finishes when the piece decays
+    pub decay: Timer,
+    /// Finishes when the piece can hit things
+    pub arming: Timer,
+}
+
+impl Debris {
+    pub fn is_armed(&self) -> bool {
+        self.arming.is_finished()
+    }
+}
+
+/// Triggered when a collector is hit so that it sheds debris, right before it is destroyed or damaged
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct ShedDebris {
+    pub collector: Entity,
+    /// Whether the collector breaks apart or only takes damage
+    pub destroyed: bool,
+}
+
+pub(super) fn shed_debris(
+    event: On<ShedDebris>,
+    mut commands: Commands,
+    config: Res<DebrisConfig>,
+    mut random: ResMut<RandomSource>,
+    collectors: Query<(&Transform, &Velocity)>,
+) {
+    let Ok((transform, velocity)) = collectors.get(event.collector) else {
+        return;
+    };
+    let pieces = if event.destroyed { config.pieces } else { config.pieces / 2 };
+    for _ in 0..pieces {
+        let spread = Vec2::from_angle(random.random_range(0.0..std::f32::consts::TAU))
+            * random.random_range(0.2..1.0)
+            * config.spread_speed;
+        commands.spawn((
+            Debris {
+                decay: Timer::from_seconds(random.random_range(config.lifetime.clone()), TimerMode::Once),
+                arming: Timer::from_seconds(config.arming_time, TimerMode::Once),
+            },
+            Name::new("Debris"),
+            Attractee,
+            Mass(0.1),
+            HitBox {
+                shape: HitBoxShape::Circle { radius: config.radius },
+                layer: CollisionLayer::Debris,
+                // pieces drift through each other and through asteroids
+                mask: CollisionMask::ALL.without(CollisionLayer::Debris).without(CollisionLayer::Asteroid),
+            },
+            Transform::from_translation(transform.translation.xy().extend(0.0)),
+            Velocity(velocity.0 + spread),
+        ));
+    }
+}
+
+pub(super) fn decay_debris(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut Debris)>) {
+    query.iter_mut().for_each(|(i_entity, mut i_debris)| {
+        i_debris.arming.tick(time.delta());
+        if i_debris.decay.tick(time.delta()).is_finished() {
+            commands.entity(i_entity).despawn();
+        }
+    });
+}
+
+/// Draw the pieces fading out as they decay
+pub(super) fn draw_debris(mut gizmos: Gizmos, query: Query<(&GlobalTransform, &HitBox, &Debris)>) {
+    query.iter().for_each(|(i_trans, i_hitbox, i_debris)| {
+        let isometry = Isometry2d::from_translation(i_trans.translation().xy());
+        let alpha = 0.3 + 0.7 * i_debris.decay.fraction_remaining();
+        gizmos.rect_2d(isometry, Vec2::splat(2.0 * i_hitbox.shape.bounding_radius()), GRAY.with_alpha(alpha));
+    });
+}
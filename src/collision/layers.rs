@@ -0,0 +1,276 @@
+//! Which bodies collide with each other and what happens when they do.
+//!
+//! Every [`HitBox`](super::HitBox) is on a [`CollisionLayer`] and has a [`CollisionMask`] of the layers it can touch.
+//! Two bodies only collide if each one's mask contains the other one's layer, and the [`CollisionRules`] decide
+//! the outcome for each pair of layers. The rules are read from [`COLLISION_RULES_FILE`] if it exists.
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Where the collision rules are read from, pairs of layers which aren't listed there keep their default rule
+pub const COLLISION_RULES_FILE: &str = "assets/collision_rules.txt";
+
+/// What kind of body a hitbox belongs to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum CollisionLayer {
+    /// The sun and planets
+    CelestialBody,
+    #[default]
+    Collector,
+    Asteroid,
+    Debris,
+}
+
+impl CollisionLayer {
+    pub const ALL: [Self; 4] = [
+        CollisionLayer::CelestialBody,
+        CollisionLayer::Collector,
+        CollisionLayer::Asteroid,
+        CollisionLayer::Debris,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollisionLayer::CelestialBody => "celestial-body",
+            CollisionLayer::Collector => "collector",
+            CollisionLayer::Asteroid => "asteroid",
+            CollisionLayer::Debris => "debris",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|layer| layer.name() == name)
+    }
+}
+
+/// The layers a hitbox can collide with
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CollisionMask(u32);
+
+impl CollisionMask {
+    pub const ALL: Self = Self(u32::MAX);
+    pub const NONE: Self = Self(0);
+
+    pub fn contains(&self, layer: CollisionLayer) -> bool {
+        self.0 & layer.bit() != 0
+    }
+
+    pub fn with(self, layer: CollisionLayer) -> Self {
+        Self(self.0 | layer.bit())
+    }
+
+    pub fn without(self, layer: CollisionLayer) -> Self {
+        Self(self.0 & !layer.bit())
+    }
+}
+
+impl Default for CollisionMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// What happens when bodies of two layers touch
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CollisionRule {
+    /// They pass through each other
+    Ignore,
+    /// The body on the first layer is destroyed, the other one is unharmed
+    DestroyFirst,
+    /// The body on the second layer is destroyed, the other one is unharmed
+    DestroySecond,
+    /// Both take damage and bounce off each other, what happens to bodies without health depends on their layer
+    /// (see [`DamageConfig::destroyed_without_health`](super::damage::DamageConfig::destroyed_without_health))
+    Damage,
+    /// Both bounce off each other unharmed
+    Bounce,
+}
+
+impl CollisionRule {
+    pub const ALL: [Self; 5] = [
+        CollisionRule::Ignore,
+        CollisionRule::DestroyFirst,
+        CollisionRule::DestroySecond,
+        CollisionRule::Damage,
+        CollisionRule::Bounce,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollisionRule::Ignore => "ignore",
+            CollisionRule::DestroyFirst => "destroy-first",
+            CollisionRule::DestroySecond => "destroy-second",
+            CollisionRule::Damage => "damage",
+            CollisionRule::Bounce => "bounce",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    /// The same rule with the layers the other way round
+    pub fn flipped(self) -> Self {
+        match self {
+            CollisionRule::DestroyFirst => CollisionRule::DestroySecond,
+            CollisionRule::DestroySecond => CollisionRule::DestroyFirst,
+            rule => rule,
+        }
+    }
+}
+
+/// The rule for every pair of layers, pairs without a rule use the fallback
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct CollisionRules {
+    rules: HashMap<(CollisionLayer, CollisionLayer), CollisionRule>,
+    pub fallback: CollisionRule,
+}
+
+impl Default for CollisionRules {
+    fn default() -> Self {
+        use CollisionLayer::*;
+        use CollisionRule::*;
+        let mut rules = Self {
+            rules: HashMap::default(),
+            fallback: Ignore,
+        };
+        rules
+            // celestial bodies don't crash into each other but destroy whatever flies into them
+            .set(CelestialBody, CelestialBody, Ignore)
+            .set(CelestialBody, Collector, DestroySecond)
+            .set(CelestialBody, Asteroid, DestroySecond)
+            .set(CelestialBody, Debris, DestroySecond)
+            .set(Collector, Collector, Damage)
+            .set(Collector, Asteroid, Damage)
+            .set(Collector, Debris, Damage)
+            // asteroids of a swarm, their fragments and debris drift through each other
+            .set(Asteroid, Asteroid, Ignore)
+            .set(Asteroid, Debris, Ignore)
+            .set(Debris, Debris, Ignore);
+        rules
+    }
+}
+
+impl CollisionRules {
+    /// Replace the rule for a pair of layers, in both orders
+    pub fn set(&mut self, first: CollisionLayer, second: CollisionLayer, rule: CollisionRule) -> &mut Self {
+        // each pair is stored in the order of the layers, so that equal rules compare equal
+        let (first, second, rule) = if (second as u32) < (first as u32) {
+            (second, first, rule.flipped())
+        } else {
+            (first, second, rule)
+        };
+        self.rules.insert((first, second), rule);
+        self
+    }
+
+    pub fn rule(&self, first: CollisionLayer, second: CollisionLayer) -> CollisionRule {
+        self.rules
+            .get(&(first, second))
+            .copied()
+            .or_else(|| self.rules.get(&(second, first)).map(|rule| rule.flipped()))
+            .unwrap_or(self.fallback)
+    }
+
+    /// Read the rules from a file, the default rules are used if it is missing or invalid
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            debug!("No collision rules at {}, using the default ones", path.display());
+            return Self::default();
+        };
+        Self::parse(&contents).unwrap_or_else(|e| {
+            warn!("Collision rules in {} are invalid, using the default ones: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    /// Write the fallback and then one `<first layer> <second layer> <rule>` line for each pair of layers with a rule
+    pub fn to_text(&self) -> String {
+        let mut result = format!("fallback {}\n", self.fallback.name());
+        for (i, first) in CollisionLayer::ALL.iter().enumerate() {
+            for second in &CollisionLayer::ALL[i..] {
+                if let Some(rule) = self.rules.get(&(*first, *second)) {
+                    let _ = writeln!(result, "{} {} {}", first.name(), second.name(), rule.name());
+                }
+            }
+        }
+        result
+    }
+
+    /// Parse rules in the format of [`CollisionRules::to_text`] on top of the default ones.
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rules = Self::default();
+        let lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for (i, line) in lines.enumerate() {
+            let invalid = || format!("invalid collision rule {}: {line}", i + 1);
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let rule = |index: usize| parts.get(index).and_then(|name| CollisionRule::from_name(name)).ok_or_else(invalid);
+            let layer = |index: usize| parts.get(index).and_then(|name| CollisionLayer::from_name(name)).ok_or_else(invalid);
+
+            match parts.as_slice() {
+                ["fallback", _] => rules.fallback = rule(1)?,
+                [_, _, _] => {
+                    rules.set(layer(0)?, layer(1)?, rule(2)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_survive_a_round_trip_through_text() {
+        let mut rules = CollisionRules::default();
+        rules.fallback = CollisionRule::Bounce;
+        rules
+            .set(CollisionLayer::Debris, CollisionLayer::Collector, CollisionRule::DestroyFirst)
+            .set(CollisionLayer::Asteroid, CollisionLayer::Asteroid, CollisionRule::Damage);
+
+        assert_eq!(CollisionRules::parse(&rules.to_text()), Ok(rules));
+    }
+
+    #[test]
+    fn missing_pairs_keep_their_default_rule() {
+        let rules = CollisionRules::parse("# asteroids bounce off each other\nasteroid asteroid bounce\n").unwrap();
+
+        assert_eq!(rules.rule(CollisionLayer::Asteroid, CollisionLayer::Asteroid), CollisionRule::Bounce);
+        assert_eq!(rules.rule(CollisionLayer::Collector, CollisionLayer::CelestialBody), CollisionRule::DestroyFirst);
+        assert_eq!(rules.fallback, CollisionRule::Ignore);
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for text in ["collector asteroid explode", "comet asteroid damage", "collector damage", "fallback"] {
+            assert!(CollisionRules::parse(text).is_err(), "{text} should be invalid");
+        }
+    }
+
+    #[test]
+    fn shipped_rules_are_the_default_ones() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(COLLISION_RULES_FILE);
+        let contents = std::fs::read_to_string(path).unwrap();
+
+        // every rule is listed so that it can be changed there
+        let listed = contents.lines().filter(|line| !line.is_empty() && !line.starts_with('#')).collect::<Vec<_>>();
+        assert_eq!(listed, CollisionRules::default().to_text().lines().collect::<Vec<_>>());
+        assert_eq!(CollisionRules::parse(&contents), Ok(CollisionRules::default()));
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_the_default_rules() {
+        assert_eq!(CollisionRules::load("does/not/exist.txt"), CollisionRules::default());
+    }
+}
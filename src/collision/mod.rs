@@ -1,43 +1,132 @@
 use crate::dev_tools::is_debug_enabled;
-use crate::physics::calc_gravity::{Attractee, Attractor};
-use crate::sun_system::{Level, Satellite, SolarSystemAssets};
+use crate::collision::broadphase::CollisionGrid;
+use crate::collision::damage::{DamageConfig, Health};
+use crate::collision::debris::{Debris, ShedDebris};
+use crate::collision::layers::{CollisionLayer, CollisionMask, CollisionRule, CollisionRules};
+use crate::collision::shape::{HitBoxShape, shape_isometry, sweep_shapes};
+use crate::collision::response::{CollisionResponseConfig, ContactCooldowns, collision_impulse, separation};
+use crate::physics::directional_forces::Mass;
+use crate::physics::velocity::Velocity;
+use crate::sun_system::asteroids::AsteroidStruck;
 use crate::{AppSystems, GameplaySystem};
 use bevy::color::palettes::basic::BLUE;
+use bevy::diagnostic::{Diagnostic, RegisterDiagnostic};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::transform::helper::TransformHelper;
 use std::collections::HashSet;
 
+pub mod broadphase;
+pub mod conjunction;
+pub mod damage;
+pub mod debris;
+pub mod layers;
+pub mod response;
+pub mod shape;
+
 pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<CollisionGrid>();
+    app.init_resource::<conjunction::ConjunctionConfig>();
+    app.init_resource::<conjunction::Conjunctions>();
+    app.register_diagnostic(Diagnostic::new(conjunction::CONJUNCTION_COST).with_suffix("ms"));
+    app.register_diagnostic(Diagnostic::new(conjunction::CONJUNCTION_SWEEPS));
+    app.init_resource::<debris::DebrisConfig>();
+    app.init_resource::<DamageConfig>();
+    app.insert_resource(CollisionRules::load(layers::COLLISION_RULES_FILE));
+    app.init_resource::<CollisionStats>();
+    app.init_resource::<CollisionResponseConfig>();
+    app.init_resource::<ContactCooldowns>();
     app.add_systems(
-        Update,
-        (check_for_collisions, draw_hitboxes.run_if(is_debug_enabled))
+        FixedUpdate,
+        (
+            update_hitbox_transforms,
+            broadphase::rebuild_collision_grid,
+            check_for_collisions,
+            record_previous_positions,
+            conjunction::predict_conjunctions,
+        )
+            .chain()
             .in_set(AppSystems::Update)
             .in_set(GameplaySystem),
     );
+    app.add_systems(FixedUpdate, debris::decay_debris.in_set(AppSystems::Update).in_set(GameplaySystem));
+    app.add_observer(debris::shed_debris);
+    app.add_observer(place_new_hitbox);
     app.add_observer(handle_fatal_collision_event);
-    app.add_observer(handle_demote_collision_event);
+    app.add_observer(count_collisions);
 }
 
-#[derive(Component, Copy, Clone, Debug, PartialEq, Default)]
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        draw_hitboxes
+            .run_if(is_debug_enabled)
+            .in_set(GameplaySystem),
+    );
+    app.add_systems(
+        Update,
+        (conjunction::draw_conjunctions, debris::draw_debris, damage::show_damage).in_set(GameplaySystem),
+    );
+    // sprites only exist when the game is presented, headless runs keep the shapes bodies are spawned with
+    app.add_systems(Update, shape::fit_hitboxes_to_sprites.in_set(GameplaySystem));
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Default)]
+#[require(PreviousPosition)]
 pub struct HitBox {
-    pub radius: f32,
+    pub shape: HitBoxShape,
+    pub layer: CollisionLayer,
+    /// The layers this hitbox can collide with
+    pub mask: CollisionMask,
 }
 
+/// Where a hitbox was during the last collision check.
+/// Used to sweep hitboxes along their path so that fast objects cannot tunnel through each other.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Default)]
+pub struct PreviousPosition(pub Option<Vec2>);
+
 #[derive(Event)]
 pub struct FatalCollisionEvent {
     pub destroyed: Entity,
     pub other: Entity,
 }
 
-#[derive(Event)]
+/// Triggered for every contact between two bodies, before any of the more specific events
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
 pub struct GeneralCollisionEvent {
-    pub destroyed: Entity,
-    pub other: Entity,
+    pub first: CollisionParticipant,
+    pub second: CollisionParticipant,
+    /// What the rules for the layers of the two bodies decided
+    pub rule: CollisionRule,
+}
+
+/// One of the two bodies of a contact
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CollisionParticipant {
+    pub entity: Entity,
+    pub layer: CollisionLayer,
+    /// Whether the contact destroys the body
+    pub destroyed: bool,
+}
+
+impl GeneralCollisionEvent {
+    pub fn participants(&self) -> [CollisionParticipant; 2] {
+        [self.first, self.second]
+    }
 }
 
+/// How many collisions happened during the match
+#[derive(Resource, Debug, Default, Copy, Clone, PartialEq)]
+pub struct CollisionStats {
+    pub collectors_destroyed: u32,
+}
+
+/// A collector was hit but survived
 #[derive(Event)]
-pub struct DemoteCollisionEvent {
-    pub demoted: Entity,
+pub struct DamageCollisionEvent {
+    pub damaged: Entity,
     pub other: Entity,
+    pub damage: f32,
 }
 
 
@@ -47,131 +136,318 @@ pub fn is_colliding(
     obj2_transform: &Transform,
     obj2_hitbox: &HitBox,
 ) -> bool {
-    let distance = obj1_transform
-        .translation
-        .distance(obj2_transform.translation);
-    if distance < (obj1_hitbox.radius + obj2_hitbox.radius) {
-        return true;
+    let isometry1 = shape_isometry(obj1_transform.translation.xy(), obj1_transform.rotation);
+    let isometry2 = shape_isometry(obj2_transform.translation.xy(), obj2_transform.rotation);
+    obj1_hitbox.shape.distance(isometry1, &obj2_hitbox.shape, isometry2) < 0.0
+}
+
+/// Sweep two circles linearly from their start to their end positions and find the time of impact.
+///
+/// The returned value is the fraction (0..=1) of the movement at which the circles first touch,
+/// or `None` if they don't touch at any point along the way.
+pub fn sweep_circles(
+    obj1_start: Vec2,
+    obj1_end: Vec2,
+    obj1_radius: f32,
+    obj2_start: Vec2,
+    obj2_end: Vec2,
+    obj2_radius: f32,
+) -> Option<f32> {
+    // treat obj2 as standing still and move obj1 relative to it
+    let offset = obj1_start - obj2_start;
+    let movement = (obj1_end - obj1_start) - (obj2_end - obj2_start);
+    let radius = obj1_radius + obj2_radius;
+
+    let c = offset.length_squared() - radius * radius;
+    if c < 0.0 {
+        // already overlapping at the start
+        return Some(0.0);
+    }
+
+    let a = movement.length_squared();
+    if a <= f32::EPSILON {
+        // not moving relative to each other
+        return None;
+    }
+
+    let b = offset.dot(movement);
+    if b >= 0.0 {
+        // moving away from each other
+        return None;
+    }
+
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
     }
-    //no collision
-    false
+
+    let toi = (-b - discriminant.sqrt()) / a;
+    (toi <= 1.0).then_some(toi)
+}
+
+/// Bring the global transforms of all hitboxes up to date.
+/// They are only propagated once per frame otherwise, which is too late for children like the asteroids of a swarm.
+fn update_hitbox_transforms(helper: TransformHelper, mut query: Query<(Entity, &mut GlobalTransform), With<HitBox>>) {
+    query.iter_mut().for_each(|(i_entity, mut i_global)| {
+        if let Ok(global) = helper.compute_global_transform(i_entity) {
+            *i_global = global;
+        }
+    });
+}
+
+/// Put new hitboxes where they belong right away, they may be spawned after the global transforms were updated in a tick
+fn place_new_hitbox(event: On<Add, HitBox>, helper: TransformHelper, mut query: Query<&mut GlobalTransform>) {
+    if let (Ok(global), Ok(mut i_global)) = (helper.compute_global_transform(event.entity), query.get_mut(event.entity)) {
+        *i_global = global;
+    }
+}
+
+/// The components of a body that take part in collisions, use with `Query<CollisionBodyData>`
+type CollisionBodyData = (
+    Entity,
+    &'static GlobalTransform,
+    &'static PreviousPosition,
+    &'static HitBox,
+    Option<&'static Mass>,
+    Option<&'static Debris>,
+);
+
+/// What decides how a collision plays out
+#[derive(SystemParam)]
+struct CollisionHandling<'w> {
+    rules: Res<'w, CollisionRules>,
+    config: Res<'w, DamageConfig>,
+    response: Res<'w, CollisionResponseConfig>,
+    cooldowns: ResMut<'w, ContactCooldowns>,
 }
 
 fn check_for_collisions(
     mut commands: Commands,
-    hitboxes: Query<(Entity, &Transform, &HitBox, Has<Attractor>, Has<Attractee>, &Level)>,
+    grid: Res<CollisionGrid>,
+    handling: CollisionHandling,
+    time: Res<Time>,
+    hitboxes: Query<CollisionBodyData>,
+    mut health_query: Query<&mut Health>,
+    mut bodies: Query<(&mut Transform, &mut Velocity)>,
 ) {
+    let CollisionHandling {
+        rules,
+        config,
+        response,
+        mut cooldowns,
+    } = handling;
+    let delta = time.delta_secs().max(f32::EPSILON);
+    cooldowns.tick(time.delta_secs());
+
+    // Sweep every pair of hitboxes that the broadphase considers close enough along the path they travelled since the last check
+    let mut contacts = Vec::new();
+    for (entity, entity_check) in grid.candidate_pairs() {
+        let Ok((_, entity_transform, entity_previous, hitbox1, ..)) = hitboxes.get(entity) else {
+            continue;
+        };
+        let Ok((_, check_transform, check_previous, hitbox2, ..)) = hitboxes.get(entity_check) else {
+            continue;
+        };
+        if !hitbox1.mask.contains(hitbox2.layer) || !hitbox2.mask.contains(hitbox1.layer) {
+            continue;
+        }
+        let entity_pos = entity_transform.translation().xy();
+        let check_pos = check_transform.translation().xy();
+        let entity_start = entity_previous.0.unwrap_or(entity_pos);
+        let check_start = check_previous.0.unwrap_or(check_pos);
+        // only the positions are remembered, so the hitboxes are swept along without turning
+        let entity_rotation = entity_transform.rotation();
+        let check_rotation = check_transform.rotation();
+        if let Some(toi) = sweep_shapes(
+            &hitbox1.shape,
+            shape_isometry(entity_start, entity_rotation),
+            shape_isometry(entity_pos, entity_rotation),
+            &hitbox2.shape,
+            shape_isometry(check_start, check_rotation),
+            shape_isometry(check_pos, check_rotation),
+        ) {
+            let relative_speed = ((entity_pos - entity_start) - (check_pos - check_start)).length() / delta;
+            contacts.push((toi, entity, entity_check, relative_speed));
+        }
+    }
+    // Resolve contacts in the order in which they happened
+    contacts.sort_by(|(toi1, a1, b1, _), (toi2, a2, b2, _)| toi1.total_cmp(toi2).then(a1.cmp(a2)).then(b1.cmp(b2)));
+
     // Track entities we already decided to destroy this system run to avoid duplicate events
     let mut destroyed_in_this_system: HashSet<Entity> = HashSet::new();
-    for (entity, entity_transform, hitbox1, isAttractor, isAttractee, level1) in hitboxes.iter() {
-        for (entity_check, check_transform, hitbox2, isAttractor2, isAttractee2, level2) in hitboxes.iter()
-        {
-            if (entity == entity_check) {
-                // no need to check collisions with self
-                continue;
+    for (toi, entity, entity_check, relative_speed) in contacts {
+        // Skip pairs where either entity is already scheduled to be destroyed in this pass
+        if destroyed_in_this_system.contains(&entity) || destroyed_in_this_system.contains(&entity_check) {
+            continue;
+        }
+        let (_, trans1, previous1, hitbox1, mass1, debris1) = hitboxes.get(entity).unwrap();
+        let (_, trans2, previous2, hitbox2, mass2, debris2) = hitboxes.get(entity_check).unwrap();
+        let rule = rules.rule(hitbox1.layer, hitbox2.layer);
+        let hits = matches!(rule, CollisionRule::Damage | CollisionRule::Bounce);
+        if hits && (debris1.is_some_and(|debris| !debris.is_armed()) || debris2.is_some_and(|debris| !debris.is_armed())) {
+            // fresh debris is still drifting away from the collision it came from
+            continue;
+        }
+        if hits && cooldowns.is_cooling_down(entity, entity_check) {
+            // the bodies already bounced off each other, a single contact only counts once
+            continue;
+        }
+
+        let mass1 = mass1.map_or(1.0, |mass| mass.0);
+        let mass2 = mass2.map_or(1.0, |mass| mass.0);
+        // collectors take damage, whether bodies without health survive depends on their layer
+        let mut damage = [None; 2];
+        let mut destroyed = [false; 2];
+        match rule {
+            CollisionRule::Ignore | CollisionRule::Bounce => {}
+            CollisionRule::DestroyFirst => destroyed[0] = true,
+            CollisionRule::DestroySecond => destroyed[1] = true,
+            CollisionRule::Damage => {
+                let participants = [(entity, hitbox1.layer, mass1, mass2), (entity_check, hitbox2.layer, mass2, mass1)];
+                for (index, (body, layer, mass, other_mass)) in participants.into_iter().enumerate() {
+                    let impact = config.impact_damage(relative_speed, mass, other_mass);
+                    destroyed[index] = match health_query.get_mut(body) {
+                        Ok(mut health) => {
+                            health.current -= impact;
+                            damage[index] = Some(impact);
+                            health.fraction() < config.destroy_threshold
+                        }
+                        Err(_) => config.destroyed_without_health.contains(layer),
+                    };
+                }
             }
-            // Skip pairs where either entity is already scheduled to be destroyed in this pass
-            if destroyed_in_this_system.contains(&entity) || destroyed_in_this_system.contains(&entity_check) {
-                continue;
+        }
+        commands.trigger(GeneralCollisionEvent {
+            first: CollisionParticipant { entity, layer: hitbox1.layer, destroyed: destroyed[0] },
+            second: CollisionParticipant { entity: entity_check, layer: hitbox2.layer, destroyed: destroyed[1] },
+            rule,
+        });
+
+        // asteroids are destroyed individually by whatever hits them, after breaking into fragments
+        if rule == CollisionRule::Damage {
+            for (body, other, layer) in [(entity, entity_check, hitbox1.layer), (entity_check, entity, hitbox2.layer)] {
+                if layer == CollisionLayer::Asteroid {
+                    commands.trigger(AsteroidStruck { asteroid: body, other });
+                }
             }
-            let distance = entity_transform
-                .translation
-                .distance(check_transform.translation);
-            if distance < (hitbox1.radius + hitbox2.radius) {
-                info!("crash");
-
-                if(isAttractor){
-                    info!("crash sun case");
-                    // first sun, sun has level 0
-                    if !destroyed_in_this_system.contains(&entity_check) {
-                        commands.trigger(FatalCollisionEvent {
-                            destroyed: entity_check,
-                            other: entity,
-                        });
-                        destroyed_in_this_system.insert(entity_check);
-                    }
-                } else if (isAttractor2) {
-                } else {
-                    info!("crash Satellites");
-
-                    // satellite 1
-                    if(level1.level == 1.) {
-                        if !destroyed_in_this_system.contains(&entity) {
-                            commands.trigger(FatalCollisionEvent {
-                                destroyed: entity,
-                                other: entity_check,
-                            });
-                            destroyed_in_this_system.insert(entity);
-                        }
-                    }else {
-                        commands.trigger(DemoteCollisionEvent {
-                            demoted: entity,
-                            other: entity_check,
-                        });
-                    }
-                    // satellite 2
-                    if level2.level == 1. {
-                        if !destroyed_in_this_system.contains(&entity_check) {
-                            commands.trigger(FatalCollisionEvent {
-                                destroyed: entity_check,
-                                other: entity,
-                            });
-                            destroyed_in_this_system.insert(entity_check);
-                        }
-                    }else {
-                        commands.trigger(DemoteCollisionEvent {
-                            demoted: entity_check,
-                            other: entity,
-                        });
+        }
+        for (index, (body, other)) in [(entity, entity_check), (entity_check, entity)].into_iter().enumerate() {
+            if let Some(damage) = damage[index] {
+                // collectors shed debris, more of it when they break apart
+                commands.trigger(ShedDebris { collector: body, destroyed: destroyed[index] });
+                if !destroyed[index] {
+                    commands.trigger(DamageCollisionEvent { damaged: body, other, damage });
+                }
+            }
+            if destroyed[index] {
+                commands.trigger(FatalCollisionEvent { destroyed: body, other });
+                destroyed_in_this_system.insert(body);
+            }
+        }
 
-                    }
+        if hits {
+            // the bodies bounce off where they touched, which changes the orbits of survivors
+            // and sends the debris of destroyed collectors away in the direction they were knocked
+            cooldowns.start(entity, entity_check, response.cooldown);
+            let pos1 = trans1.translation().xy();
+            let pos2 = trans2.translation().xy();
+            let start1 = previous1.0.unwrap_or(pos1);
+            let start2 = previous2.0.unwrap_or(pos2);
+            let contact1 = start1.lerp(pos1, toi);
+            let contact2 = start2.lerp(pos2, toi);
+            let normal = (contact1 - contact2).normalize_or_zero();
+            // bodies without a velocity of their own, like the asteroids of a swarm, move along with their parent
+            let velocity1 = bodies.get(entity).map_or((pos1 - start1) / delta, |(_, velocity)| velocity.0);
+            let velocity2 = bodies.get(entity_check).map_or((pos2 - start2) / delta, |(_, velocity)| velocity.0);
+            let restitution = (response.restitution(hitbox1.layer) + response.restitution(hitbox2.layer)) / 2.0;
+            let (change1, change2) =
+                collision_impulse(normal, velocity1, velocity2, mass1, mass2, restitution).unwrap_or_default();
+            let overlap = -hitbox1
+                .shape
+                .distance(shape_isometry(contact1, trans1.rotation()), &hitbox2.shape, shape_isometry(contact2, trans2.rotation()))
+                .min(0.0);
+            let (push1, push2) = separation(normal, overlap, mass1, mass2);
+            for (body, position, change) in [(entity, contact1 + push1, change1), (entity_check, contact2 + push2, change2)] {
+                if let Ok((mut i_trans, mut i_velocity)) = bodies.get_mut(body) {
+                    i_trans.translation = position.extend(i_trans.translation.z);
+                    i_velocity.0 += change;
                 }
             }
         }
     }
 }
 
-fn handle_demote_collision_event(event: On<DemoteCollisionEvent>, mut commands: Commands, mut collector_query: Query<(Entity, &mut Level, &mut Sprite),  (With<Attractee>)>, assets: Res<SolarSystemAssets>) {
-    let demoted_entity= commands
-        .get_entity(event.demoted)
-        .expect("Wanted to demote entity after collision but entity does not exist!") ;
-    for (entity, mut level, mut sprite) in collector_query.iter_mut() {
-        if demoted_entity.id() == entity {
-            if (level.level > 1.) {
-                level.level -= 1.;
-            }
-            if level.level == 1. {
-                //frm 2 to 1
-                *sprite = Sprite::from(assets.collector.clone());
-            } else if(level.level == 2.){
-                //reduce from lv3 to 2
-               *sprite =  Sprite::from(assets.collector2.clone());
-            } else{
-                *sprite =  Sprite::from(assets.collector3.clone());
-            }
+/// Bodies that bounced off each other were moved after their global transforms were updated, so compute them again
+fn record_previous_positions(helper: TransformHelper, mut query: Query<(Entity, &mut GlobalTransform, &mut PreviousPosition)>) {
+    query.iter_mut().for_each(|(i_entity, mut i_global, mut i_previous)| {
+        if let Ok(global) = helper.compute_global_transform(i_entity) {
+            *i_global = global;
         }
-    }
-    // todo Get sprite and replace it with the correct level sprite
+        i_previous.0 = Some(i_global.translation().xy());
+    });
 }
 
-fn handle_fatal_collision_event(event: On<FatalCollisionEvent>, mut commands: Commands) {
-    commands
-        .get_entity(event.destroyed)
-        .expect("Wanted to despawn entity after fatal collision but entity does not exist!")
-        .despawn();
+fn count_collisions(event: On<GeneralCollisionEvent>, mut stats: ResMut<CollisionStats>) {
+    stats.collectors_destroyed += event
+        .participants()
+        .iter()
+        .filter(|participant| participant.destroyed && participant.layer == CollisionLayer::Collector)
+        .count() as u32;
 }
 
+fn handle_fatal_collision_event(event: On<FatalCollisionEvent>, mut commands: Commands) {
+    // the body might already be gone or about to be, e.g. debris that decayed or a body that escaped in the same tick
+    if let Ok(mut entity) = commands.get_entity(event.destroyed) {
+        entity.try_despawn();
+    }
+}
 
-/**
-collectors have hp, output, level
-lower hp decreases output
-fornow collectors die when colliding with each other
-**/
 
-fn draw_hitboxes(mut gizmos: Gizmos, query: Query<(&Transform, &HitBox)>) {
+fn draw_hitboxes(mut gizmos: Gizmos, query: Query<(&GlobalTransform, &HitBox)>) {
     query.iter().for_each(|(i_trans, i_hitbox)| {
-        let isometry = Isometry2d::new(i_trans.translation.xy(), Rot2::default());
+        let isometry = shape_isometry(i_trans.translation().xy(), i_trans.rotation());
         let color = BLUE;
-        gizmos.circle_2d(isometry, i_hitbox.radius, color);
+        match &i_hitbox.shape {
+            HitBoxShape::Circle { radius } => {
+                gizmos.circle_2d(isometry, *radius, color);
+            }
+            HitBoxShape::Capsule { half_length, radius } => {
+                gizmos.primitive_2d(&Capsule2d::new(*radius, 2.0 * half_length), isometry, color);
+            }
+            HitBoxShape::ConvexPolygon { vertices } => {
+                let outline = vertices.iter().chain(vertices.first()).map(|vertex| isometry * *vertex);
+                gizmos.linestrip_2d(outline, color);
+            }
+        }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_fast_bodies_passing_through_each_other_are_swept() {
+        // two small bodies swap places within a single step, they don't overlap at either end
+        let (start1, end1) = (Vec2::new(-10.0, 0.0), Vec2::new(10.0, 0.0));
+        let (start2, end2) = (Vec2::new(10.0, 0.5), Vec2::new(-10.0, 0.5));
+        assert!(start1.distance(start2) > 2.0 && end1.distance(end2) > 2.0);
+
+        let toi = sweep_circles(start1, end1, 1.0, start2, end2, 1.0).expect("the bodies should have touched on the way");
+        let gap = start1.lerp(end1, toi).distance(start2.lerp(end2, toi));
+        assert!((gap - 2.0).abs() < 1e-4, "the bodies should just touch at the time of impact, but are {gap} apart");
+        assert!(toi < 0.5, "they touch before they pass each other");
+    }
+
+    #[test]
+    fn bodies_passing_beside_each_other_are_not_swept() {
+        let toi = sweep_circles(Vec2::new(-10.0, 0.0), Vec2::new(10.0, 0.0), 1.0, Vec2::new(10.0, 2.5), Vec2::new(-10.0, 2.5), 1.0);
+        assert_eq!(toi, None);
+    }
+
+    #[test]
+    fn overlapping_bodies_touch_right_away() {
+        let toi = sweep_circles(Vec2::ZERO, Vec2::X, 1.0, Vec2::new(1.5, 0.0), Vec2::new(5.0, 0.0), 1.0);
+        assert_eq!(toi, Some(0.0));
+    }
+}
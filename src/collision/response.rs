@@ -0,0 +1,81 @@
+//! How bodies bounce off each other when they collide.
+//!
+//! An impulse along the line between the bodies changes their velocities, so a collision alters their orbits,
+//! and they are pushed apart so they don't overlap anymore. A short cooldown per pair of bodies makes sure that
+//! a single contact only counts once, even if the bodies are still touching in the next ticks.
+
+use crate::collision::layers::CollisionLayer;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+/// How bouncy collisions are and how long a contact lasts
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct CollisionResponseConfig {
+    /// Coefficient of restitution of collectors, 0 is perfectly inelastic and 1 perfectly elastic
+    pub collector_restitution: f32,
+    pub asteroid_restitution: f32,
+    pub debris_restitution: f32,
+    /// Seconds after a contact during which the same two bodies can't collide again
+    pub cooldown: f32,
+}
+
+impl Default for CollisionResponseConfig {
+    fn default() -> Self {
+        Self {
+            collector_restitution: 0.5,
+            asteroid_restitution: 0.3,
+            debris_restitution: 0.1,
+            cooldown: 0.5,
+        }
+    }
+}
+
+impl CollisionResponseConfig {
+    pub fn restitution(&self, layer: CollisionLayer) -> f32 {
+        match layer {
+            CollisionLayer::Asteroid => self.asteroid_restitution,
+            CollisionLayer::Debris => self.debris_restitution,
+            // celestial bodies are so heavy that how bouncy they are hardly matters
+            CollisionLayer::CelestialBody | CollisionLayer::Collector => self.collector_restitution,
+        }
+    }
+}
+
+/// Pairs of bodies that recently collided and for how many seconds they can't collide again
+#[derive(Resource, Debug, Default)]
+pub struct ContactCooldowns(HashMap<(Entity, Entity), f32>);
+
+impl ContactCooldowns {
+    pub fn tick(&mut self, delta: f32) {
+        self.0.retain(|_, remaining| {
+            *remaining -= delta;
+            *remaining > 0.0
+        });
+    }
+
+    pub fn start(&mut self, a: Entity, b: Entity, seconds: f32) {
+        self.0.insert((a.min(b), a.max(b)), seconds);
+    }
+
+    pub fn is_cooling_down(&self, a: Entity, b: Entity) -> bool {
+        self.0.contains_key(&(a.min(b), a.max(b)))
+    }
+}
+
+/// Changes of velocity of two bodies bouncing off each other with the given restitution.
+///
+/// `normal` points from the second body to the first. Returns `None` if the bodies are already moving apart.
+pub fn collision_impulse(normal: Vec2, velocity1: Vec2, velocity2: Vec2, mass1: f32, mass2: f32, restitution: f32) -> Option<(Vec2, Vec2)> {
+    let closing = (velocity1 - velocity2).dot(normal);
+    if closing >= 0.0 {
+        return None;
+    }
+    let impulse = -(1.0 + restitution) * closing / (1.0 / mass1 + 1.0 / mass2);
+    Some((normal * impulse / mass1, -normal * impulse / mass2))
+}
+
+/// How far each of two overlapping bodies has to move along `normal` so that they just touch, the lighter one moves more
+pub fn separation(normal: Vec2, overlap: f32, mass1: f32, mass2: f32) -> (Vec2, Vec2) {
+    let share1 = mass2 / (mass1 + mass2);
+    (normal * overlap * share1, -normal * overlap * (1.0 - share1))
+}
@@ -0,0 +1,465 @@
+//! The shapes of hitboxes and how to tell whether two of them touch.
+//!
+//! Every shape is a convex core, a point, a segment or a polygon, grown by a radius. Two shapes touch when their
+//! cores are closer than the sum of their radii. Shapes are given in world units around the body they belong to
+//! and turn with it, but don't scale with its transform. [`HitBoxFromSprite`] fits a shape to the opaque pixels
+//! of a body's sprite instead.
+
+use crate::collision::{HitBox, sweep_circles};
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+
+/// The outline of a hitbox around the center of its body
+#[derive(Debug, Clone, PartialEq)]
+pub enum HitBoxShape {
+    Circle { radius: f32 },
+    /// Two half circles joined by a rectangle, along the local y axis like bevys `Capsule2d`
+    Capsule { half_length: f32, radius: f32 },
+    /// Vertices in counterclockwise order
+    ConvexPolygon { vertices: Vec<Vec2> },
+}
+
+impl Default for HitBoxShape {
+    fn default() -> Self {
+        HitBoxShape::Circle { radius: 0.0 }
+    }
+}
+
+/// Shapes closer than this count as touching while they are swept
+const SWEEP_TOLERANCE: f32 = 1e-3;
+/// How often two shapes are advanced towards each other before giving up,
+/// only paths that barely graze each other take that many
+const SWEEP_MAX_ITERATIONS: usize = 32;
+
+impl HitBoxShape {
+    /// Radius of the smallest circle around the center of the body that contains the whole shape
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            HitBoxShape::Circle { radius } => *radius,
+            HitBoxShape::Capsule { half_length, radius } => half_length + radius,
+            HitBoxShape::ConvexPolygon { vertices } => vertices.iter().map(|vertex| vertex.length()).fold(0.0, f32::max),
+        }
+    }
+
+    /// The core of the shape placed in the world and the radius it is grown by
+    fn core(&self, isometry: Isometry2d) -> (Vec<Vec2>, f32) {
+        match self {
+            HitBoxShape::Circle { radius } => (vec![isometry.translation], *radius),
+            HitBoxShape::Capsule { half_length, radius } => (
+                vec![isometry * Vec2::new(0.0, -half_length), isometry * Vec2::new(0.0, *half_length)],
+                *radius,
+            ),
+            HitBoxShape::ConvexPolygon { vertices } => (vertices.iter().map(|vertex| isometry * *vertex).collect(), 0.0),
+        }
+    }
+
+    /// Gap between the outlines of two placed shapes, negative by how deep they overlap
+    pub fn distance(&self, isometry: Isometry2d, other: &HitBoxShape, other_isometry: Isometry2d) -> f32 {
+        let (core1, radius1) = self.core(isometry);
+        let (core2, radius2) = other.core(other_isometry);
+        core_distance(&core1, &core2) - radius1 - radius2
+    }
+}
+
+/// Place a shape at a position and turned like a body with `rotation`
+pub fn shape_isometry(position: Vec2, rotation: Quat) -> Isometry2d {
+    let x_axis = rotation * Vec3::X;
+    Isometry2d::new(position, Rot2::from_sin_cos(x_axis.y, x_axis.x).normalize())
+}
+
+/// Distance between two convex cores given by their vertices, negative by how deep they overlap
+fn core_distance(core1: &[Vec2], core2: &[Vec2]) -> f32 {
+    let mut distance = f32::INFINITY;
+    for (a1, b1) in edges(core1) {
+        for (a2, b2) in edges(core2) {
+            distance = distance.min(segment_distance(a1, b1, a2, b2));
+        }
+    }
+    if distance > 0.0 && !contains(core1, core2[0]) && !contains(core2, core1[0]) {
+        return distance;
+    }
+    -penetration_depth(core1, core2)
+}
+
+/// How far two overlapping cores have to be moved apart until they only touch, along the axis where that is the
+/// least (separating axis theorem). Points and segments lying on each other don't overlap by anything.
+fn penetration_depth(core1: &[Vec2], core2: &[Vec2]) -> f32 {
+    let project = |core: &[Vec2], axis: Vec2| {
+        core.iter()
+            .map(|vertex| vertex.dot(axis))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| (min.min(x), max.max(x)))
+    };
+    let depth = edges(core1)
+        .chain(edges(core2))
+        .filter_map(|(a, b)| (b - a).perp().try_normalize())
+        .map(|axis| {
+            let (min1, max1) = project(core1, axis);
+            let (min2, max2) = project(core2, axis);
+            (max1 - min2).min(max2 - min1)
+        })
+        .fold(f32::INFINITY, f32::min);
+    if depth.is_finite() { depth.max(0.0) } else { 0.0 }
+}
+
+/// The edges of a core, a single point is an edge of length zero
+fn edges(core: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    let count = if core.len() == 2 { 1 } else { core.len() };
+    (0..count).map(move |i| (core[i], core[(i + 1) % core.len()]))
+}
+
+/// Whether a counterclockwise polygon contains a point, points and segments contain nothing
+fn contains(polygon: &[Vec2], point: Vec2) -> bool {
+    polygon.len() >= 3 && edges(polygon).all(|(a, b)| (b - a).perp_dot(point - a) >= 0.0)
+}
+
+fn segment_distance(a1: Vec2, b1: Vec2, a2: Vec2, b2: Vec2) -> f32 {
+    let d1 = b1 - a1;
+    let d2 = b2 - a2;
+    // segments that cross each other
+    let denominator = d1.perp_dot(d2);
+    if denominator.abs() > f32::EPSILON {
+        let t = (a2 - a1).perp_dot(d2) / denominator;
+        let u = (a2 - a1).perp_dot(d1) / denominator;
+        if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+            return 0.0;
+        }
+    }
+    point_segment_distance(a1, a2, b2)
+        .min(point_segment_distance(b1, a2, b2))
+        .min(point_segment_distance(a2, a1, b1))
+        .min(point_segment_distance(b2, a1, b1))
+}
+
+fn point_segment_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let segment = b - a;
+    let t = if segment.length_squared() > f32::EPSILON {
+        ((point - a).dot(segment) / segment.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    point.distance(a + segment * t)
+}
+
+/// Sweep two hitboxes linearly from their start to their end placements and find the time of impact.
+///
+/// Their bounding circles are swept first, from where those touch the shapes are advanced conservatively:
+/// each step only goes as far as the gap between them could possibly be closed, given how fast they move and
+/// turn, so even thin shapes can't skip past each other. Returns the fraction (0..=1) of the movement or `None`
+/// if they don't touch.
+pub fn sweep_shapes(
+    shape1: &HitBoxShape,
+    start1: Isometry2d,
+    end1: Isometry2d,
+    shape2: &HitBoxShape,
+    start2: Isometry2d,
+    end2: Isometry2d,
+) -> Option<f32> {
+    let toi = sweep_circles(
+        start1.translation,
+        end1.translation,
+        shape1.bounding_radius(),
+        start2.translation,
+        end2.translation,
+        shape2.bounding_radius(),
+    )?;
+    if matches!((shape1, shape2), (HitBoxShape::Circle { .. }, HitBoxShape::Circle { .. })) {
+        return Some(toi);
+    }
+    let placement = |start: Isometry2d, end: Isometry2d, t: f32| {
+        Isometry2d::new(start.translation.lerp(end.translation, t), start.rotation.slerp(end.rotation, t))
+    };
+    // no point of either shape moves faster than this towards the other one over the whole movement
+    let closing_speed = ((end1.translation - start1.translation) - (end2.translation - start2.translation)).length()
+        + start1.rotation.angle_to(end1.rotation).abs() * shape1.bounding_radius()
+        + start2.rotation.angle_to(end2.rotation).abs() * shape2.bounding_radius();
+
+    let mut t = toi;
+    for _ in 0..SWEEP_MAX_ITERATIONS {
+        let gap = shape1.distance(placement(start1, end1, t), shape2, placement(start2, end2, t));
+        if gap <= SWEEP_TOLERANCE {
+            return Some(t);
+        }
+        if closing_speed <= f32::EPSILON {
+            return None;
+        }
+        t += gap / closing_speed;
+        if t > 1.0 {
+            return None;
+        }
+    }
+    None
+}
+
+/// Fit the hitbox of a body to the opaque pixels of its sprite, whenever the sprite is loaded, changes or is scaled.
+///
+/// Bodies without a loaded sprite, e.g. in headless runs, keep the shape they were spawned with.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+#[require(FittedSprite)]
+pub enum HitBoxFromSprite {
+    /// The smallest circle around the center that contains every opaque pixel
+    Circle,
+    /// The convex hull of the opaque pixels
+    ConvexHull,
+}
+
+/// The sprite image and scale a hitbox was last fitted to, `None` until it has been fitted
+#[derive(Component, Debug, Default, Copy, Clone, PartialEq)]
+pub struct FittedSprite(Option<(AssetId<Image>, Vec2)>);
+
+/// Pixels with less alpha than this don't count as part of the outline
+const OPAQUE_ALPHA: f32 = 0.5;
+
+pub(super) fn fit_hitboxes_to_sprites(
+    mut image_events: MessageReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+    mut outlines: Local<HashMap<AssetId<Image>, Vec<Vec2>>>,
+    mut query: Query<(&HitBoxFromSprite, &Sprite, &Transform, &mut HitBox, &mut FittedSprite)>,
+) {
+    // images that were (re)loaded have to be outlined again
+    let reloaded = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    outlines.retain(|id, _| !reloaded.contains(id));
+
+    query.iter_mut().for_each(|(i_fit, i_sprite, i_trans, mut i_hitbox, mut i_fitted)| {
+        let id = i_sprite.image.id();
+        let scale = i_trans.scale.xy();
+        if i_fitted.0 == Some((id, scale)) && !reloaded.contains(&id) {
+            return;
+        }
+        if !outlines.contains_key(&id) {
+            let Some(image) = images.get(id) else {
+                return;
+            };
+            outlines.insert(id, opaque_outline(image));
+        }
+        i_fitted.0 = Some((id, scale));
+        let vertices: Vec<Vec2> = outlines[&id].iter().map(|vertex| *vertex * scale).collect();
+        if vertices.len() < 3 {
+            return;
+        }
+        i_hitbox.shape = match i_fit {
+            HitBoxFromSprite::Circle => HitBoxShape::Circle {
+                radius: vertices.iter().map(|vertex| vertex.length()).fold(0.0, f32::max),
+            },
+            HitBoxFromSprite::ConvexHull => HitBoxShape::ConvexPolygon { vertices },
+        };
+    });
+}
+
+/// Convex hull of the opaque pixels of an image in pixels around its center, counterclockwise with y pointing up
+fn opaque_outline(image: &Image) -> Vec<Vec2> {
+    let (width, height) = (image.width(), image.height());
+    let center = Vec2::new(width as f32, height as f32) / 2.0;
+    // the leftmost and rightmost opaque pixel of each row are enough for the hull
+    let mut points = Vec::new();
+    for y in 0..height {
+        let opaque = |x: &u32| image.get_color_at(*x, y).is_ok_and(|color| color.alpha() >= OPAQUE_ALPHA);
+        let (Some(left), Some(right)) = ((0..width).find(opaque), (0..width).rev().find(opaque)) else {
+            continue;
+        };
+        for x in [left as f32, right as f32 + 1.0] {
+            points.push(Vec2::new(x - center.x, center.y - y as f32));
+            points.push(Vec2::new(x - center.x, center.y - y as f32 - 1.0));
+        }
+    }
+    convex_hull(points)
+}
+
+/// Andrew's monotone chain, returns the hull counterclockwise
+fn convex_hull(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<Vec2> = Vec::new();
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2 && (hull[hull.len() - 1] - hull[hull.len() - 2]).perp_dot(point - hull[hull.len() - 2]) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+    use crate::collision::is_colliding;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    /// A rectangle 40 units wide and only 0.2 high
+    fn thin_plank() -> HitBoxShape {
+        HitBoxShape::ConvexPolygon {
+            vertices: vec![Vec2::new(-20.0, -0.1), Vec2::new(20.0, -0.1), Vec2::new(20.0, 0.1), Vec2::new(-20.0, 0.1)],
+        }
+    }
+
+    #[test]
+    fn thin_shapes_do_not_tunnel_through_small_ones() {
+        // the plank flies through the pebble in a single step, it is far bigger than its own thickness past it
+        // when checked at the ends or in a few steps after the bounding circles touch
+        let pebble = HitBoxShape::Circle { radius: 0.5 };
+        let toi = sweep_shapes(
+            &thin_plank(),
+            Isometry2d::from_translation(Vec2::new(0.0, -50.0)),
+            Isometry2d::from_translation(Vec2::new(0.0, 50.0)),
+            &pebble,
+            Isometry2d::IDENTITY,
+            Isometry2d::IDENTITY,
+        )
+        .expect("the plank should hit the pebble");
+
+        // they touch once the plank is 0.6 below the center of the pebble
+        assert!((toi - 0.494).abs() < 1e-3, "touched at {toi}");
+    }
+
+    #[test]
+    fn turning_shapes_are_swept_until_they_touch() {
+        // a long thin capsule standing on the origin turns a quarter to the right onto a pebble
+        let stick = HitBoxShape::Capsule { half_length: 10.0, radius: 0.1 };
+        let pebble = HitBoxShape::Circle { radius: 0.5 };
+        let toi = sweep_shapes(
+            &stick,
+            Isometry2d::IDENTITY,
+            Isometry2d::from_rotation(Rot2::radians(-FRAC_PI_2)),
+            &pebble,
+            Isometry2d::from_translation(Vec2::new(5.0, 0.0)),
+            Isometry2d::from_translation(Vec2::new(5.0, 0.0)),
+        )
+        .expect("the stick should hit the pebble");
+
+        // the stick touches the pebble once it is less than 0.6 away from it
+        let angle = (1.0 - toi) * FRAC_PI_2;
+        assert!((5.0 * angle.sin() - 0.6).abs() < 0.01, "touched at {toi}, {angle} before lying flat");
+    }
+
+    #[test]
+    fn thin_shapes_passing_beside_each_other_do_not_touch() {
+        let pebble = HitBoxShape::Circle { radius: 0.5 };
+        let toi = sweep_shapes(
+            &thin_plank(),
+            Isometry2d::from_translation(Vec2::new(21.0, -50.0)),
+            Isometry2d::from_translation(Vec2::new(21.0, 50.0)),
+            &pebble,
+            Isometry2d::IDENTITY,
+            Isometry2d::IDENTITY,
+        );
+        assert_eq!(toi, None);
+    }
+
+    /// A fully opaque square image
+    fn square(size: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    #[test]
+    fn hitboxes_are_only_fitted_again_when_their_sprite_changes() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<Messages<AssetEvent<Image>>>();
+        let image = world.resource_mut::<Assets<Image>>().add(square(4));
+        let body = world
+            .spawn((
+                HitBoxFromSprite::Circle,
+                Sprite::from_image(image.clone()),
+                Transform::from_scale(Vec3::splat(2.0)),
+                HitBox::default(),
+            ))
+            .id();
+        let fit = world.register_system(fit_hitboxes_to_sprites);
+        let radius = |world: &World| world.get::<HitBox>(body).unwrap().shape.bounding_radius();
+        let refitted = |world: &mut World| {
+            world.clear_trackers();
+            world.run_system(fit).unwrap();
+            world.entity(body).get_ref::<HitBox>().unwrap().is_changed()
+        };
+
+        // the corners of the square are 2 pixels away from its center in both directions
+        assert!(refitted(&mut world));
+        assert!((radius(&world) - 4.0 * 2f32.sqrt()).abs() < 1e-4);
+        assert!(!refitted(&mut world), "nothing changed");
+
+        world.get_mut::<Transform>(body).unwrap().translation = Vec3::new(10.0, 0.0, 0.0);
+        assert!(!refitted(&mut world), "moving doesn't change the outline");
+
+        world.get_mut::<Transform>(body).unwrap().scale = Vec3::splat(3.0);
+        assert!(refitted(&mut world));
+        assert!((radius(&world) - 6.0 * 2f32.sqrt()).abs() < 1e-4);
+
+        world.resource_mut::<Assets<Image>>().insert(image.id(), square(2)).unwrap();
+        world.write_message(AssetEvent::Modified { id: image.id() });
+        assert!(refitted(&mut world));
+        assert!((radius(&world) - 3.0 * 2f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hitboxes_only_touch_where_their_shapes_are() {
+        let circle = HitBox {
+            shape: HitBoxShape::Circle { radius: 1.0 },
+            ..default()
+        };
+        let capsule = HitBox {
+            shape: HitBoxShape::Capsule { half_length: 5.0, radius: 1.0 },
+            ..default()
+        };
+        // a long thin panel along the x axis
+        let panel = HitBox {
+            shape: HitBoxShape::ConvexPolygon {
+                vertices: vec![Vec2::new(-8.0, -1.0), Vec2::new(8.0, -1.0), Vec2::new(8.0, 1.0), Vec2::new(-8.0, 1.0)],
+            },
+            ..default()
+        };
+        let at = |x: f32, y: f32| Transform::from_xyz(x, y, 0.0);
+        let turned = Transform::from_rotation(Quat::from_rotation_z(PI / 2.0));
+
+        assert!(is_colliding(&at(0.0, 0.0), &panel, &at(8.5, 0.0), &circle), "the tip of the panel");
+        assert!(!is_colliding(&at(0.0, 0.0), &panel, &at(0.0, 5.0), &circle), "inside the bounding circle but beside the panel");
+        assert!(is_colliding(&turned, &panel, &at(0.0, 8.5), &circle), "the panel turns with its body");
+        assert!(!is_colliding(&turned, &panel, &at(8.5, 0.0), &circle));
+        assert!(is_colliding(&at(0.0, 0.0), &capsule, &at(0.0, 6.5), &circle), "the end of the capsule");
+        assert!(!is_colliding(&at(0.0, 0.0), &capsule, &at(3.0, 0.0), &circle));
+        assert!(is_colliding(&at(0.0, 0.0), &panel, &at(0.0, 5.5), &capsule), "panel and capsule crossing");
+
+        assert!(is_colliding(&at(0.0, 0.0), &panel, &turned, &panel), "panels crossing each other");
+        let small = HitBox {
+            shape: HitBoxShape::ConvexPolygon {
+                vertices: vec![Vec2::new(-0.5, -0.5), Vec2::new(0.5, -0.5), Vec2::new(0.5, 0.5), Vec2::new(-0.5, 0.5)],
+            },
+            ..default()
+        };
+        assert!(is_colliding(&at(0.0, 0.0), &panel, &at(3.0, 0.0), &small), "a polygon inside another one");
+        assert!(is_colliding(&at(0.0, 0.0), &panel, &at(0.0, 1.5), &panel), "panels lying on each other");
+        assert!(!is_colliding(&at(0.0, 0.0), &panel, &at(0.0, 2.5), &panel));
+
+        // overlapping polygons are as far apart as they have to be moved to only touch
+        let overlap = |transform: Transform, other: &HitBox| {
+            let isometry = shape_isometry(transform.translation.xy(), transform.rotation);
+            -panel.shape.distance(Isometry2d::IDENTITY, &other.shape, isometry)
+        };
+        assert!((overlap(at(0.0, 1.5), &panel) - 0.5).abs() < 1e-4);
+        assert!((overlap(at(3.0, 0.2), &small) - 1.3).abs() < 1e-4);
+        assert!((overlap(turned, &panel) - 9.0).abs() < 1e-4, "crossing panels are pushed out lengthwise");
+    }
+}
@@ -1,13 +1,16 @@
 //! Development tools for the game. This plugin is only enabled in dev builds.
 
 use crate::Pause;
+use crate::collision::conjunction::{CONJUNCTION_COST, CONJUNCTION_SWEEPS};
 use crate::screens::Screen;
+use crate::sun_system::navigation_instruments::{PROJECTION_COST, PROJECTION_UPDATES};
 use bevy::{
-    dev_tools::states::log_transitions, input::common_conditions::input_just_pressed, prelude::*, 
+    dev_tools::states::log_transitions, diagnostic::DiagnosticsStore, input::common_conditions::input_just_pressed,
+    prelude::*, 
 };
 //use crate::screens::Screen;
 
-const TOGGLE_KEY: KeyCode = KeyCode::KeyD;
+const TOGGLE_KEY: KeyCode = KeyCode::Backquote;
 
 pub(super) fn plugin(app: &mut App) {
     app.add_systems(Update, log_transitions::<Screen>);
@@ -19,6 +22,63 @@ pub(super) fn plugin(app: &mut App) {
             .run_if(in_state(Screen::Gameplay))
             .run_if(input_just_pressed(TOGGLE_KEY)),
     );
+
+    app.add_systems(OnEnter(Screen::Gameplay), spawn_debug_stats);
+    app.add_systems(Update, update_debug_stats.run_if(in_state(Screen::Gameplay)));
+}
+
+/// Text in the debug overlay showing how expensive parts of the game are
+#[derive(Component)]
+struct DebugStatsText;
+
+fn spawn_debug_stats(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Debug Stats"),
+        Text::new(""),
+        TextFont {
+            font_size: 12.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(80.0),
+            left: Val::Px(15.0),
+            ..default()
+        },
+        Visibility::Hidden,
+        Pickable::IGNORE,
+        DebugStatsText,
+        DespawnOnExit(Screen::Gameplay),
+    ));
+}
+
+fn update_debug_stats(
+    options: Res<UiDebugOptions>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut query: Query<(&mut Text, &mut Visibility), With<DebugStatsText>>,
+) {
+    let Ok((mut text, mut visibility)) = query.single_mut() else {
+        return;
+    };
+    if !options.enabled {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+
+    let average = |path| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    text.0 = format!(
+        "projections: {:.2} ms, {:.1} updated per frame\nconjunctions: {:.2} ms, {:.0} sweeps per prediction",
+        average(&PROJECTION_COST),
+        average(&PROJECTION_UPDATES),
+        average(&CONJUNCTION_COST),
+        average(&CONJUNCTION_SWEEPS),
+    );
+    *visibility = Visibility::Inherited;
 }
 
 pub fn is_debug_enabled(options: Res<UiDebugOptions>) -> bool {
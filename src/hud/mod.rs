@@ -1,10 +1,16 @@
 use crate::GameplaySystem;
-use crate::collision::FatalCollisionEvent;
-use crate::launching::{LaunchPad, LaunchState};
+use crate::boundary::LostToSpaceEvent;
+use crate::collision::GeneralCollisionEvent;
+use crate::collision::conjunction::Conjunctions;
+use crate::collision::debris::Debris;
+use crate::launching::{CollectorId, LaunchPad, LaunchState};
 use crate::score::Score;
 use crate::screens::Screen;
 use crate::sun_system::SolarSystemAssets;
-use crate::sun_system::asteroids::AsteroidSwarmSpawned;
+use crate::sun_system::thruster::THRUSTER_KEYS;
+use crate::replay::SimulationClock;
+use crate::sun_system::asteroids::{Asteroid, AsteroidDirector, AsteroidSwarmSpawned};
+use crate::sun_system::autopilot::AutopilotFailed;
 use bevy::prelude::*;
 use bevy::ui_render::stack_z_offsets::BORDER;
 
@@ -15,12 +21,13 @@ impl Plugin for HudPlugin {
         app.add_systems(OnEnter(Screen::Gameplay), setup_hud)
             .add_systems(
                 Update,
-                (update_hud, update_crash_indicators, update_launch_pad_ui, update_zoom_level, update_explanation_text, update_debris_warning).in_set(GameplaySystem),
+                (update_hud, update_crash_indicators, update_launch_pad_ui, update_zoom_level, update_explanation_text, update_debris_warning, update_conjunction_warning, update_wave_forecast).in_set(GameplaySystem),
             );
-        app.add_observer(handle_fatal_collision_event_for_hud);
+        app.add_observer(handle_collision_event_for_hud);
         app.add_observer(handle_asteroid_swarm_spawned);
+        app.add_observer(handle_autopilot_failed);
+        app.add_observer(handle_lost_to_space);
         app.insert_resource(HudState {
-            just_destroyed: None,
             already_pressed_space: false,
             already_pressed_lmb: false,
         });
@@ -54,7 +61,6 @@ struct ExplanationContainer;
 
 #[derive(Resource)]
 struct HudState {
-    just_destroyed: Option<Entity>,
     already_pressed_space: bool,
     already_pressed_lmb: bool,
 }
@@ -67,6 +73,19 @@ struct DebrisWarning {
     timer: Timer,
 }
 
+#[derive(Component)]
+struct ConjunctionWarning;
+
+#[derive(Component)]
+struct ConjunctionWarningText;
+
+#[derive(Component)]
+struct WaveForecastText;
+
+/// The text of the warning in the middle of the screen, shared by all warnings
+#[derive(Component)]
+struct WarningText;
+
 fn setup_hud(mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>) {
     // TOP LEFT: Energy Rate and Total Energy Storage
     let container = commands.spawn((
@@ -120,6 +139,37 @@ fn setup_hud(mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>
         ],
     ));
 
+    // TOP LEFT, BELOW THE ENERGY: when the next asteroids arrive
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(155.0),
+            left: Val::Px(15.0),
+            width: Val::Px(330.0),
+            padding: UiRect::axes(Val::Px(15.0), Val::Px(8.0)),
+            border: UiRect::all(Val::Px(BORDER)),
+            ..default()
+        },
+        Pickable::IGNORE,
+        BackgroundColor(Color::srgb(0.0, 0.0, 0.0)),
+        Outline {
+            width: Val::Px(2.0),
+            offset: Default::default(),
+            color: Color::xyz(0.4811, 0.3064, 0.0253),
+        },
+        children![(
+            Text::new(""),
+            TextFont {
+                font: solar_system_assets.font.clone(),
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::xyz(0.4811, 0.3064, 0.0253)),
+            Pickable::IGNORE,
+            WaveForecastText,
+        )],
+    ));
+
     let text_center = Justify::Center;
 
     // BOTTOM RIGHT: Launch Pad UI
@@ -215,6 +265,45 @@ fn setup_hud(mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>
         ],
     ));
 
+    //TOP CENTER: UPCOMING COLLISIONS
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(15.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Pickable::IGNORE,
+        Visibility::Hidden,
+        ConjunctionWarning,
+        children![(
+            Node {
+                padding: UiRect::all(Val::Px(10.0)),
+                border: UiRect::all(Val::Px(BORDER)),
+                ..default()
+            },
+            Pickable::IGNORE,
+            BackgroundColor(Color::srgb(0.0, 0.0, 0.0)),
+            Outline {
+                width: Val::Px(2.0),
+                offset: Default::default(),
+                color: Color::xyz(0.4811, 0.3064, 0.0253),
+            },
+            children![(
+                Text::new(""),
+                TextFont {
+                    font: solar_system_assets.font.clone(),
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::xyz(0.4811, 0.3064, 0.0253)),
+                Pickable::IGNORE,
+                ConjunctionWarningText,
+            )],
+        )],
+    ));
+
     //MIDDLE OF SCREEN: Explaination text
     commands.spawn((
         Node {
@@ -279,8 +368,10 @@ fn setup_hud(mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>
         children![
             (
                 Node {
-                    width: Val::Px(300.0),
-                    height: Val::Px(60.0),
+                    // grows for longer warnings
+                    min_width: Val::Px(300.0),
+                    min_height: Val::Px(60.0),
+                    padding: UiRect::horizontal(Val::Px(15.0)),
                     border: UiRect::all(Val::Px(BORDER)),
                     justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
@@ -303,6 +394,7 @@ fn setup_hud(mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>
                         },
                         TextColor(Color::xyz(0.4811, 0.3064, 0.0253)),
                         Pickable::IGNORE,
+                        WarningText,
                     )
                 ],
             )
@@ -353,26 +445,23 @@ fn get_ascii_bar(percentage: f32) -> String {
     format!("{}{}", filled_part, empty_part)
 }
 
-fn handle_fatal_collision_event_for_hud(
-    event: On<FatalCollisionEvent>,
+fn handle_collision_event_for_hud(
+    event: On<GeneralCollisionEvent>,
     mut commands: Commands,
-    entity_query: Query<(&Transform, Entity)>,
+    entity_query: Query<&GlobalTransform>,
     solar_system_assets: Res<SolarSystemAssets>,
-    mut just_destroyed: ResMut<HudState>,
 ) {
-    let (entity_transform, _) = entity_query
-        .get(event.destroyed)
-        .expect("Wanted to get transform of destroyed entity but entity does not exist!");
-
-    if just_destroyed.just_destroyed == Some(event.other) {
-        //already showing crash indicator for the other entity; skipping to avoid overlapping indicators
+    // one indicator per crash, where the first of the bodies broke apart
+    let Some(destroyed) = event.participants().into_iter().find(|participant| participant.destroyed) else {
         return;
-    }
-
+    };
+    let Ok(entity_transform) = entity_query.get(destroyed.entity) else {
+        return;
+    };
 
     commands.spawn((
         Name::new("crash"),
-        Transform::from_translation(entity_transform.translation).with_scale(Vec3::splat(0.01)),
+        Transform::from_translation(entity_transform.translation()).with_scale(Vec3::splat(0.01)),
         Sprite::from(solar_system_assets.crash.clone()),
         CrashIndicator {
             timer: Timer::from_seconds(0.15, TimerMode::Repeating),
@@ -381,15 +470,12 @@ fn handle_fatal_collision_event_for_hud(
         },
         Visibility::Visible,
     ));
-
-    just_destroyed.just_destroyed = Some(event.destroyed);
 }
 
 fn update_crash_indicators(
     mut commands: Commands,
     time: Res<Time>,
     mut query: Query<(Entity, &mut CrashIndicator, &mut Visibility)>,
-    mut hud_state: ResMut<HudState>,
 ) {
     for (entity, mut crash_indicator, mut visibility) in query.iter_mut() {
         crash_indicator.timer.tick(time.delta());
@@ -409,7 +495,6 @@ fn update_crash_indicators(
                 crash_indicator.blink_count += 1;
             } else {
                 commands.entity(entity).despawn();
-                hud_state.just_destroyed = None;
             }
         }
     }
@@ -477,10 +562,10 @@ fn update_explanation_text(
     if !hud_state.already_pressed_space {
         if mouse_input.pressed(MouseButton::Left) {
             hud_state.already_pressed_space = true;
-            explanation_text.0 = "PRESS SPACE TO SLOW DOWN".to_string();
+            explanation_text.0 = "PRESS SPACE TO SLOW DOWN, WASD TO STEER".to_string();
         }
     } else if !hud_state.already_pressed_lmb {
-        if keyboard_input.pressed(KeyCode::Space) {
+        if keyboard_input.any_pressed(THRUSTER_KEYS.map(|(key, _)| key)) {
             hud_state.already_pressed_lmb = true;
             *container_visibility = Visibility::Hidden;
         }
@@ -504,13 +589,110 @@ fn update_debris_warning(
     }
 }
 
+/// List the soonest predicted collisions so that the player can react in time
+fn update_conjunction_warning(
+    conjunctions: Res<Conjunctions>,
+    collectors: Query<&CollectorId>,
+    asteroids: Query<(), With<Asteroid>>,
+    debris: Query<(), With<Debris>>,
+    mut warning_query: Query<&mut Visibility, With<ConjunctionWarning>>,
+    mut text_query: Query<&mut Text, With<ConjunctionWarningText>>,
+) {
+    let (Ok(mut visibility), Ok(mut text)) = (warning_query.single_mut(), text_query.single_mut()) else {
+        return;
+    };
+
+    // bodies that crashed since the last prediction are gone and skipped
+    let name = |entity| match collectors.get(entity) {
+        Ok(id) => Some(format!("COLLECTOR {}", id.0 + 1)),
+        Err(_) if asteroids.contains(entity) => Some("ASTEROIDS".to_string()),
+        Err(_) => debris.contains(entity).then(|| "DEBRIS".to_string()),
+    };
+    let lines = conjunctions
+        .0
+        .iter()
+        .filter_map(|conjunction| {
+            Some(format!(
+                "COLLISION IN {:.0}S: {} / {}",
+                conjunction.in_seconds.ceil(),
+                name(conjunction.a)?,
+                name(conjunction.b)?
+            ))
+        })
+        .take(3)
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        *visibility = Visibility::Hidden;
+    } else {
+        text.0 = lines.join("\n");
+        *visibility = Visibility::Inherited;
+    }
+}
+
+/// Tell the player when the next asteroid wave arrives and how big it is
+fn update_wave_forecast(
+    clock: Res<SimulationClock>,
+    director: Res<AsteroidDirector>,
+    mut text_query: Query<&mut Text, With<WaveForecastText>>,
+) {
+    let Ok(mut text) = text_query.single_mut() else {
+        return;
+    };
+    let forecast = match director.forecast(clock.elapsed) {
+        Some((in_seconds, wave)) => format!(
+            "NEXT DEBRIS IN {:.0}S\n{} SWARM{} OF {}-{} ASTEROIDS",
+            in_seconds.ceil(),
+            wave.swarms,
+            if wave.swarms == 1 { "" } else { "S" },
+            wave.asteroids.start,
+            wave.asteroids.end.saturating_sub(1),
+        ),
+        None => "NO DEBRIS EXPECTED".to_string(),
+    };
+    if text.0 != forecast {
+        text.0 = forecast;
+    }
+}
+
 fn handle_asteroid_swarm_spawned(
     _trigger: On<AsteroidSwarmSpawned>,
+    query: Query<(&mut DebrisWarning, &mut Visibility)>,
+    text: Query<&mut Text, With<WarningText>>,
+) {
+    show_warning(query, text, "DEBRIS WARNING ! !".to_string());
+}
+
+fn handle_autopilot_failed(
+    trigger: On<AutopilotFailed>,
+    query: Query<(&mut DebrisWarning, &mut Visibility)>,
+    text: Query<&mut Text, With<WarningText>>,
+) {
+    show_warning(query, text, format!("AUTOPILOT FAILED\n{}", trigger.error.to_string().to_uppercase()));
+}
+
+fn handle_lost_to_space(
+    trigger: On<LostToSpaceEvent>,
+    collectors: Query<&CollectorId>,
+    query: Query<(&mut DebrisWarning, &mut Visibility)>,
+    text: Query<&mut Text, With<WarningText>>,
+) {
+    if let Ok(id) = collectors.get(trigger.entity) {
+        show_warning(query, text, format!("COLLECTOR {} LOST TO SPACE", id.0 + 1));
+    }
+}
+
+fn show_warning(
     mut query: Query<(&mut DebrisWarning, &mut Visibility)>,
+    mut text: Query<&mut Text, With<WarningText>>,
+    message: String,
 ) {
     let Ok((mut warning, mut visibility)) = query.single_mut() else {
         return;
     };
+    if let Ok(mut text) = text.single_mut() {
+        text.0 = message;
+    }
 
     warning.timer.reset();
     *visibility = Visibility::Visible;
@@ -2,18 +2,27 @@ use std::fmt::Debug;
 use bevy::color::palettes::basic::GREEN;
 use bevy::color::palettes::css::WHITE;
 use crate::GameplaySystem;
+use crate::replay::{accepts_player_input, ApplyPlayerInput, PendingInputs, PlayerInput, ReplayMode};
 use crate::collision::HitBox;
+use crate::collision::layers::CollisionLayer;
+use crate::collision::shape::{HitBoxFromSprite, HitBoxShape};
+use crate::collision::damage::Health;
 use crate::physics::calc_gravity::Attractee;
-use crate::physics::directional_forces::{GravityForce, Mass};
+use crate::physics::directional_forces::Mass;
 use crate::physics::velocity::Velocity;
 use crate::score::{EnergyRateLabel, Score};
+use crate::sun_system::autopilot::Autopilot;
+use crate::sun_system::maneuver::ManeuverNode;
 use crate::sun_system::navigation_instruments::NavigationInstruments;
-use crate::sun_system::thruster::{Thruster, ThrusterDirection};
+use crate::sun_system::thruster::Thruster;
 use crate::sun_system::{Level, Satellite, SolarSystemAssets};
 use bevy::input::common_conditions::{input_just_pressed, input_just_released};
 use bevy::prelude::*;
+use bevy::transform::helper::TransformHelper;
 use bevy::window::PrimaryWindow;
 
+/// Where collectors are launched from. Attached as a child to the body it sits on,
+/// launched collectors inherit the velocity of that body.
 #[derive(Component)]
 pub struct LaunchPad;
 
@@ -22,8 +31,15 @@ pub struct LaunchPad;
 #[derive(Resource)]
 pub struct LaunchState {
     pub launched_at_time: Option<f64>,
+    /// How many collectors have been launched so far, used to hand out [`CollectorId`]s
+    pub launched: u32,
 }
 
+/// Identifies a collector by the order in which it was launched.
+/// Unlike `Entity` this is stable between runs and can be used in recorded inputs.
+#[derive(Component, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CollectorId(pub u32);
+
 #[derive(Component)]
 pub struct CollectorStats {
     pub energy_rate: f32,
@@ -38,21 +54,42 @@ pub struct Fuel {
 #[derive(Component)]
 pub struct FuelLabel;
 
+/// Outline of the body and solar panels of a level 1 collector, replaced by the outline of its sprite once that is loaded
+const COLLECTOR_OUTLINE: [Vec2; 8] = [
+    Vec2::new(-7.8, -3.5),
+    Vec2::new(-7.0, -5.0),
+    Vec2::new(-1.2, -4.3),
+    Vec2::new(4.3, -1.1),
+    Vec2::new(7.8, 3.6),
+    Vec2::new(6.9, 5.0),
+    Vec2::new(1.1, 4.3),
+    Vec2::new(-4.3, 1.2),
+];
+
 
 pub(super) fn plugin(app: &mut App) {
+    app.add_observer(launch_collector);
+    app.add_observer(select_collector);
+    app.insert_resource(LaunchState {
+        launched_at_time: None,
+        launched: 0,
+    });
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
     app.add_systems(
         Update,
         (
-            start_new_launch.run_if(input_just_released(MouseButton::Left)),
+            start_new_launch
+                .run_if(input_just_released(MouseButton::Left))
+                .run_if(accepts_player_input),
             record_launch_time.run_if(input_just_pressed(MouseButton::Left)),
-            deactivate_old_sats.run_if(input_just_released(MouseButton::Left)),
             update_fuel_label,
+            update_collector_sprite,
         )
             .in_set(GameplaySystem),
     );
-    app.insert_resource(LaunchState {
-        launched_at_time: None,
-    });
+    app.add_observer(decorate_collector);
 }
 
 pub fn make_launchpad() -> impl Bundle {
@@ -64,18 +101,16 @@ pub fn make_launchpad() -> impl Bundle {
 }
 
 fn start_new_launch(
-    mut commands: Commands,
-    launch_pad_query: Query<&Transform, With<LaunchPad>>,
+    launch_pad_query: Query<&GlobalTransform, With<LaunchPad>>,
     window: Single<&Window, With<PrimaryWindow>>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
-    solar_system_assets: Res<SolarSystemAssets>,
     mut launch_state: ResMut<LaunchState>,
+    mut pending_inputs: ResMut<PendingInputs>,
     time: Res<Time>,
-    mut score: ResMut<Score>,
 ) {
 
     let launch_pad_transform = launch_pad_query.single().unwrap();
-    let launch_position = launch_pad_transform.translation;
+    let launch_position = launch_pad_transform.translation();
 
     let (camera, camera_transform) = camera_query.single().unwrap();
 
@@ -89,8 +124,6 @@ fn start_new_launch(
         return;
     };
 
-    info!("Launching new satellite towards {:?}", launch_direction);
-
     //force is dependent on how long the mouse was held down
     let mut force_multiplier = if let Some(launch_start_time) = launch_state.launched_at_time {
         let held_duration = time.elapsed_secs_f64() - launch_start_time;
@@ -100,17 +133,53 @@ fn start_new_launch(
     };
 
     force_multiplier = force_multiplier * 10.0;
-    let sprite ;
+
+    // the actual launch happens in the next simulation tick
+    pending_inputs.push(PlayerInput::Launch {
+        direction: launch_direction.xy(),
+        force: force_multiplier as f32,
+    });
+
+    launch_state.launched_at_time = None;
+}
+
+fn launch_collector(
+    input: On<ApplyPlayerInput>,
+    mut commands: Commands,
+    launch_pad_query: Query<(Entity, &ChildOf), With<LaunchPad>>,
+    body_query: Query<(Option<&Velocity>, Option<&HitBox>)>,
+    transform_helper: TransformHelper,
+    old_sats_query: Query<(Entity, &Level, FlownByCommand), With<NavigationInstruments>>,
+    mut launch_state: ResMut<LaunchState>,
+    mut score: ResMut<Score>,
+) {
+    let PlayerInput::Launch { direction, force } = input.0 else {
+        return;
+    };
+
+    // the global transform is only propagated once per frame but the body the pad sits on moves every tick
+    let (launch_pad, launch_pad_parent) = launch_pad_query.single().unwrap();
+    let launch_position = transform_helper.compute_global_transform(launch_pad).unwrap().translation();
+    let launch_direction = direction.extend(0.0);
+    let (body_velocity, body_hitbox) = body_query.get(launch_pad_parent.parent()).unwrap_or_default();
+    let body_velocity = body_velocity.map(|velocity| velocity.0).unwrap_or_default();
+    let hitbox = HitBox {
+        shape: HitBoxShape::ConvexPolygon { vertices: COLLECTOR_OUTLINE.to_vec() },
+        layer: CollisionLayer::Collector,
+        ..default()
+    };
+    // start clear of the body so that the collector doesn't immediately crash into it
+    let clearance = body_hitbox.map_or(0.0, |body_hitbox| body_hitbox.shape.bounding_radius()) + hitbox.shape.bounding_radius() + 1.0;
+
+    info!("Launching new satellite towards {:?}", launch_direction);
+
     let lvl ;
     if (score.energy_stored > 10000. && score.energy_stored <20000.){
         lvl=2.;
-        sprite = solar_system_assets.collector2.clone();
     }else if (score.energy_stored >20000.){
         lvl=3.;
-        sprite = solar_system_assets.collector3.clone();
     }else{
         lvl=1.;
-        sprite= solar_system_assets.collector.clone();        
     }
     info!("Pay energy");
     if (score.energy_stored >= 0.2) {
@@ -118,29 +187,55 @@ fn start_new_launch(
     } else {
         return;
     }
-let collector_id = commands.spawn((
+
+    // only the newest satellite can be controlled
+    for (entity, level, (planned, autopilot)) in old_sats_query.iter() {
+        deselect_collector(&mut commands, entity, level, planned || autopilot);
+    }
+
+    let id = CollectorId(launch_state.launched);
+    launch_state.launched += 1;
+
+commands.spawn((
+        id,
         Fuel { amount: 1.5 },
         Level { level: lvl },
         Attractee,
-        GravityForce::default(),
-        Velocity(launch_direction.xy() * Vec2::splat(force_multiplier as f32)),
+        Velocity(body_velocity + launch_direction.xy() * Vec2::splat(force)),
         Mass(1.0),
-        Transform::from_translation(launch_position + launch_direction)
+        Transform::from_translation(launch_position + launch_direction * clearance)
             .with_scale(Vec3::splat(0.015)),
-        Sprite::from(sprite),
-        TextColor(Color::from(GREEN)),
-        Thruster::new(ThrusterDirection::Retrograde, 2.0),
-        HitBox { radius: 4.0 },
+        Thruster::for_level(&Level { level: lvl }),
+        Health::for_level(&Level { level: lvl }),
+        hitbox,
+        HitBoxFromSprite::ConvexHull,
         NavigationInstruments,
         Satellite,
         CollectorStats {
             energy_rate: 0.0,
             total_collected: 0.0,
         },
-        Pickable::default(),
-    ))
-        .observe(on_hover_collector_over)
-        .id();
+    ));
+}
+
+/// Give a freshly launched collector its sprite, labels and make it selectable
+fn decorate_collector(
+    event: On<Add, Satellite>,
+    mut commands: Commands,
+    level_query: Query<&Level>,
+    solar_system_assets: Res<SolarSystemAssets>,
+) {
+    let collector_id = event.entity;
+    let level = level_query.get(collector_id).map(|level| level.level).unwrap_or(1.);
+
+    commands
+        .entity(collector_id)
+        .insert((
+            Sprite::from(collector_sprite(&solar_system_assets, level)),
+            TextColor(Color::from(GREEN)),
+            Pickable::default(),
+        ))
+        .observe(on_hover_collector_over);
 
     commands.spawn((
         Text2d::new("0"),
@@ -168,29 +263,81 @@ let collector_id = commands.spawn((
         Visibility::Visible,
         Pickable::IGNORE,
     ));
+}
 
-    launch_state.launched_at_time = None;
+fn collector_sprite(assets: &SolarSystemAssets, level: f32) -> Handle<Image> {
+    if level >= 3. {
+        assets.collector3.clone()
+    } else if level >= 2. {
+        assets.collector2.clone()
+    } else {
+        assets.collector.clone()
+    }
+}
+
+/// Collectors whose level changed since the last check
+type LeveledUpCollector = (With<Satellite>, Changed<Level>);
+
+/// Swap the sprite of collectors whose level changed
+fn update_collector_sprite(
+    mut collector_query: Query<(&Level, &mut Sprite), LeveledUpCollector>,
+    solar_system_assets: Res<SolarSystemAssets>,
+) {
+    for (level, mut sprite) in collector_query.iter_mut() {
+        *sprite = Sprite::from(collector_sprite(&solar_system_assets, level.level));
+    }
 }
 
 fn on_hover_collector_over(
     ev: On<Pointer<Over>>,
-    mut commands: Commands,
-    query: Query<Entity, (With<NavigationInstruments>, With<Thruster>)>,
+    collector_query: Query<&CollectorId>,
+    mode: Res<ReplayMode>,
+    mut pending_inputs: ResMut<PendingInputs>,
 ) {
+    if mode.is_replaying() {
+        return;
+    }
 
-    println!("hover over collector {:?}", ev.entity);
-    commands.entity(ev.entity).insert(NavigationInstruments);
-    commands.entity(ev.entity).insert(Thruster::new(ThrusterDirection::Retrograde, 2.0));
+    if let Ok(collector_id) = collector_query.get(ev.entity) {
+        pending_inputs.push(PlayerInput::Select { collector: collector_id.0 });
+    }
+}
 
-    //remove it from all other satellites
-    for entity in query.iter() {
-        if entity != ev.entity {
-            commands.entity(entity).remove::<NavigationInstruments>();
-            commands.entity(entity).remove::<Thruster>();
+fn select_collector(
+    input: On<ApplyPlayerInput>,
+    mut commands: Commands,
+    query: Query<(Entity, &CollectorId, &Level, FlownByCommand)>,
+) {
+    let PlayerInput::Select { collector } = input.0 else {
+        return;
+    };
+
+    for (entity, collector_id, level, (planned, autopilot)) in query.iter() {
+        if collector_id.0 == collector {
+            commands.entity(entity).insert(NavigationInstruments);
+            commands.entity(entity).insert(Thruster::for_level(level));
+        } else {
+            //remove it from all other satellites
+            deselect_collector(&mut commands, entity, level, planned || autopilot);
         }
     }
 }
 
+/// Whether a collector has a planned burn and whether the autopilot flies it
+type FlownByCommand = (Has<ManeuverNode>, Has<Autopilot>);
+
+/// Take the controls away from a collector. Planned burns and the autopilot still fly it,
+/// with a thruster the player can't fire.
+fn deselect_collector(commands: &mut Commands, entity: Entity, level: &Level, commanded: bool) {
+    let mut collector = commands.entity(entity);
+    collector.remove::<NavigationInstruments>();
+    if commanded {
+        collector.insert(Thruster::for_level(level));
+    } else {
+        collector.remove::<Thruster>();
+    }
+}
+
 
 fn record_launch_time(time: Res<Time>, mut launch_state: ResMut<LaunchState>, score: Res<Score>) {
     if (score.energy_stored < 0.2) {
@@ -201,17 +348,6 @@ fn record_launch_time(time: Res<Time>, mut launch_state: ResMut<LaunchState>, sc
     }
 }
 
-fn deactivate_old_sats(
-    mut commands: Commands,
-    thruster_query: Query<Entity, (With<Thruster>, With<NavigationInstruments>)>,
-) {
-    for entity in thruster_query.iter() {
-        let mut ec = commands.get_entity(entity).unwrap();
-        ec.remove::<Thruster>();
-        ec.remove::<NavigationInstruments>();
-    }
-}
-
 fn update_fuel_label(
     collector_query: Query<(&Fuel, &Children), With<CollectorStats>>,
     mut label_query: Query<(&mut Text2d, &mut Visibility), With<FuelLabel>>,
@@ -0,0 +1,198 @@
+// Support configuring Bevy lints within code.
+#![cfg_attr(bevy_lint, feature(register_tool), register_tool(bevy))]
+
+mod asset_tracking;
+pub mod boundary;
+pub mod collision;
+#[cfg(feature = "dev")]
+mod dev_tools;
+mod hud;
+pub mod launching;
+pub mod physics;
+pub mod replay;
+pub mod score;
+pub mod screens;
+pub mod sun_system;
+mod sound;
+mod trails;
+
+use std::ops::{Deref, DerefMut};
+use crate::physics::calc_gravity::GravityModel;
+use crate::replay::ReplayOptions;
+use crate::screens::Screen;
+use bevy::log::LogPlugin;
+use bevy::state::app::StatesPlugin;
+use bevy::window::WindowResolution;
+use bevy::{asset::AssetMetaCheck, prelude::*};
+use rand_chacha::ChaCha8Rng;
+
+/// The complete game including window, rendering, audio and player input.
+pub struct AppPlugin;
+
+impl Plugin for AppPlugin {
+    fn build(&self, app: &mut App) {
+        // Configure bevys default plugins
+        app.add_plugins(
+            DefaultPlugins
+                .set(AssetPlugin {
+                    // Wasm builds will check for meta files (that don't exist) if this isn't set.
+                    // This causes errors and even panics on web build on itch.
+                    // See https://github.com/bevyengine/bevy_github_ci_template/issues/48.
+                    meta_check: AssetMetaCheck::Never,
+                    ..default()
+                })
+                .set(WindowPlugin {
+                    primary_window: Window {
+                        title: "Type two".to_string(),
+                        fit_canvas_to_parent: true,
+                        //mode: WindowMode::Fullscreen(MonitorSelection::Primary, VideoModeSelection::Current), Laggy
+                        resolution: WindowResolution::new(1024, 576),
+                        ..default()
+                    }
+                    .into(),
+                    ..default()
+                })
+                .set(LogPlugin {
+                    filter: "info,ldjam58=debug".to_string(),
+                    ..default()
+                }),
+        );
+        app.insert_resource(ClearColor(Color::srgb(0.0, 0.0, 0.0)));
+
+        // the game itself
+        app.add_plugins(SimulationPlugin {
+            replay: ReplayOptions::from_args(std::env::args().skip(1)),
+            // so that collectors can be parked around the earth
+            gravity: GravityModel::PatchedConics,
+        });
+
+        // and everything that presents it to the player
+        app.add_plugins((
+            asset_tracking::plugin,
+            physics::presentation_plugin,
+            #[cfg(feature = "dev")]
+            dev_tools::plugin,
+            screens::presentation_plugin,
+            sun_system::presentation_plugin,
+            launching::presentation_plugin,
+            collision::presentation_plugin,
+            boundary::presentation_plugin,
+            hud::HudPlugin,
+            sound::SoundPlugin,
+            trails::TrailsPlugin,
+        ));
+    }
+}
+
+/// The simulation of the game without any window, rendering, audio or player input.
+///
+/// This can be run headless, e.g. under `MinimalPlugins`, and be controlled by pushing
+/// [`PlayerInput`](replay::PlayerInput)s into [`PendingInputs`](replay::PendingInputs).
+#[derive(Default)]
+pub struct SimulationPlugin {
+    /// How the simulation is seeded and whether it is recorded or replayed
+    pub replay: ReplayOptions,
+    /// How the pulls of several attractors are combined
+    pub gravity: GravityModel,
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+
+        app.add_plugins((
+            physics::PhysicsPlugin {
+                gravity: self.gravity,
+                ..default()
+            },
+            replay::ReplayPlugin {
+                options: self.replay.clone(),
+            },
+            screens::plugin,
+            sun_system::plugin,
+            launching::plugin,
+            collision::plugin,
+            boundary::plugin,
+            score::plugin,
+        ));
+        // Tell bevy that our AppSystems should always be executed in the below order
+        app.configure_sets(
+            Update,
+            (
+                AppSystems::RecordInput,
+                AppSystems::Physics,
+                AppSystems::Update,
+            )
+                .chain(),
+        );
+        app.configure_sets(
+            FixedUpdate,
+            (
+                AppSystems::RecordInput,
+                AppSystems::Physics,
+                AppSystems::Update,
+            )
+                .chain(),
+        );
+
+        // Tell all of our used bevy schedules that they should only run Gameplay systems if we're in the gameplay screen
+        app.configure_sets(PreUpdate, GameplaySystem.run_if(in_state(Screen::Gameplay)));
+        app.configure_sets(Update, GameplaySystem.run_if(in_state(Screen::Gameplay)));
+        app.configure_sets(
+            PostUpdate,
+            GameplaySystem.run_if(in_state(Screen::Gameplay)),
+        );
+        app.configure_sets(
+            FixedUpdate,
+            GameplaySystem.run_if(in_state(Screen::Gameplay)),
+        );
+
+        // Set up the `Pause` state.
+        app.init_state::<Pause>();
+        app.configure_sets(Update, PausableSystems.run_if(in_state(Pause(false))));
+
+        // The randomness source is set up by the replay plugin so that it can be seeded explicitly
+    }
+}
+
+/// High-level groupings/tags of systems for the app in the `Update` and `FixedUpdate` schedules.
+#[derive(SystemSet, Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum AppSystems {
+    /// Record player input.
+    RecordInput,
+    /// Calculate physical forces based on entity components
+    Physics,
+    /// Do everything else (consider splitting this into further variants).
+    Update,
+}
+
+/// Whether or not the game is paused.
+#[derive(States, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Pause(pub bool);
+
+/// A system set for systems that shouldn't run while the game is paused.
+#[derive(SystemSet, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct PausableSystems;
+
+/// A system set which marks systems that should only run during gameplay i.e. not during the loading screen
+#[derive(SystemSet, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct GameplaySystem;
+
+#[derive(Resource)]
+pub struct RandomSource(ChaCha8Rng);
+
+impl Deref for RandomSource {
+    type Target = ChaCha8Rng;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RandomSource {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
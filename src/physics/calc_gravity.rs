@@ -1,41 +1,280 @@
-use bevy::ecs::query::QuerySingleError;
 use crate::physics::directional_forces::{GravityForce, Mass};
+use crate::physics::orbital_elements::OrbitalElements;
+use crate::physics::velocity::Velocity;
 use bevy::prelude::*;
 
 #[derive(Component, Debug)]
 pub struct Attractor;
 
 #[derive(Component, Debug, Copy, Clone)]
+#[require(GravityForce, OrbitalElements)]
 pub struct Attractee;
 
+/// The region around an attractor which itself orbits a bigger body (e.g. a planet around the sun)
+/// in which it is the body that is orbited (patched conics).
+/// Attractors without a sphere of influence dominate everywhere outside of the spheres of other attractors.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Default)]
+pub struct SphereOfInfluence {
+    pub radius: f32,
+}
+
+/// The components that make up a [`GravitySource`], use with `Query<GravitySourceData, With<Attractor>>`
+pub type GravitySourceData = (
+    Entity,
+    &'static Mass,
+    &'static Transform,
+    Option<&'static Velocity>,
+    Option<&'static SphereOfInfluence>,
+);
+
+/// Newtons gravitational constant
+pub const GRAVITATIONAL_CONSTANT: f32 = 6.674e-11;
+
+/// A snapshot of a body that pulls on others.
+/// Collected once per tick so that gravity can be summed over all attractors without holding queries.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GravitySource {
+    pub entity: Entity,
+    pub mass: Mass,
+    pub position: Vec2,
+    /// `None` for bodies that stay in place
+    pub velocity: Option<Vec2>,
+    /// `None` for bodies that dominate everywhere outside of other spheres of influence
+    pub sphere_of_influence: Option<f32>,
+}
+
+impl GravitySource {
+    /// The source after `dt` seconds, assuming it keeps moving with its current velocity
+    pub fn extrapolate(&self, dt: f32) -> Self {
+        Self {
+            position: self.position + self.velocity.unwrap_or_default() * dt,
+            ..*self
+        }
+    }
+}
+
+/// How the pulls of several attractors on a body are combined
+#[derive(Resource, Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum GravityModel {
+    /// Every attractor pulls on every body (n-body)
+    #[default]
+    NBody,
+    /// Only the attractor whose sphere of influence a body is in pulls on it (patched conics).
+    ///
+    /// Orbits around a planet are unaffected by the sun, so collectors can be parked around the earth, which is why
+    /// the game uses this model. With
+    /// n-body gravity the pull of the sun tears such orbits apart within seconds, since the earth is so light
+    /// that it only dominates right above its surface. In exchange, leaving a sphere of influence changes the
+    /// pull abruptly.
+    PatchedConics,
+}
+
+impl GravityModel {
+    /// Gravitational pull on a body at `position`.
+    /// `exclude` can be used to ignore the body itself if it is also an attractor (e.g. a planet).
+    pub fn force(&self, sources: &[GravitySource], exclude: Option<Entity>, attractee_mass: &Mass, position: Vec2) -> Vec2 {
+        match self {
+            GravityModel::NBody => calc_total_gravity_force(sources, exclude, attractee_mass, position),
+            GravityModel::PatchedConics => calc_patched_gravity_force(sources, exclude, attractee_mass, position),
+        }
+    }
+}
+
 pub(super) fn apply_gravity(
-    attractor: Query<(&Mass, &Transform), ((With<Attractor>, Without<Attractee>), Without<GravityForce>)>,
-    mut attractee: Query<(&Mass, &Transform, &mut GravityForce), (Without<Attractor>, With<Attractee>)>,
+    gravity: Res<GravityModel>,
+    attractor: Query<GravitySourceData, With<Attractor>>,
+    mut attractee: Query<(Entity, &Mass, &Transform, &mut GravityForce), With<Attractee>>,
 ) {
-    let attractor = match attractor.single() {
-        Ok(ok) => ok,
-        Err(QuerySingleError::NoEntities(_)) => { return },
-        Err(QuerySingleError::MultipleEntities(e)) => panic!("Found multiple attractors in world but n-body physics is not supported: {e}"),
-    };
+    let sources = collect_gravity_sources(attractor.iter());
+    if sources.is_empty() {
+        return;
+    }
 
-    attractee.iter_mut().for_each(|(i_mass, i_transform, mut i_gravity_force)| {
-        i_gravity_force.0 = calc_gravity_force(attractor.0, attractor.1, i_mass, i_transform);
+    attractee.iter_mut().for_each(|(i_entity, i_mass, i_transform, mut i_gravity_force)| {
+        i_gravity_force.0 = gravity.force(&sources, Some(i_entity), i_mass, i_transform.translation.xy());
     });
 }
 
-pub fn calc_gravity_force(attractor_mass: &Mass, attractor_transform: &Transform, attractee_mass: &Mass, attractee_transform: &Transform) -> Vec2 {
-    let pos_attractor = attractor_transform.translation.xy();
-    let pos_attractee = attractee_transform.translation.xy();
+pub fn collect_gravity_sources<'a>(
+    attractors: impl Iterator<
+        Item = (
+            Entity,
+            &'a Mass,
+            &'a Transform,
+            Option<&'a Velocity>,
+            Option<&'a SphereOfInfluence>,
+        ),
+    >,
+) -> Vec<GravitySource> {
+    attractors
+        .map(|(entity, mass, transform, velocity, sphere_of_influence)| GravitySource {
+            entity,
+            mass: *mass,
+            position: transform.translation.xy(),
+            velocity: velocity.map(|velocity| velocity.0),
+            sphere_of_influence: sphere_of_influence.map(|sphere| sphere.radius),
+        })
+        .collect()
+}
+
+/// Sum the gravitational pull of all `sources` on a body at `position`.
+/// `exclude` can be used to ignore the body itself if it is also an attractor (e.g. a planet).
+pub fn calc_total_gravity_force(sources: &[GravitySource], exclude: Option<Entity>, attractee_mass: &Mass, position: Vec2) -> Vec2 {
+    sources
+        .iter()
+        .filter(|source| Some(source.entity) != exclude)
+        .map(|source| calc_gravity_force(&source.mass, source.position, attractee_mass, position))
+        .sum()
+}
 
+/// Gravitational pull on a body at `position` using patched conics.
+///
+/// Only the body that is orbited at `position` pulls. If that body moves itself (e.g. a planet around the sun)
+/// the body is pulled along with it, so that orbits within a sphere of influence are unaffected by the bigger body.
+/// `exclude` can be used to ignore the body itself if it is also an attractor (e.g. a planet).
+pub fn calc_patched_gravity_force(sources: &[GravitySource], exclude: Option<Entity>, attractee_mass: &Mass, position: Vec2) -> Vec2 {
+    let Some(reference) = dominant_body(sources, exclude, position) else {
+        return Vec2::ZERO;
+    };
+
+    let mut force = calc_gravity_force(&reference.mass, reference.position, attractee_mass, position);
+    if reference.velocity.is_some()
+        && let Some(parent) = dominant_body(sources, Some(reference.entity), reference.position)
+    {
+        let reference_acceleration = calc_gravity_force(&parent.mass, parent.position, &reference.mass, reference.position) / reference.mass.0;
+        force += reference_acceleration * attractee_mass.0;
+    }
+    force
+}
+
+/// Find the body that is orbited at `position`, i.e. the one that is used as the reference for an orbit.
+/// This doesn't change how bodies are pulled unless gravity is modelled with [`GravityModel::PatchedConics`].
+///
+/// This is the attractor with the smallest sphere of influence containing `position`.
+/// Outside of all spheres of influence it is the attractor without one whose pull dominates.
+pub fn dominant_body(sources: &[GravitySource], exclude: Option<Entity>, position: Vec2) -> Option<&GravitySource> {
+    let candidates = || sources.iter().filter(move |source| Some(source.entity) != exclude);
+    let pull = |source: &GravitySource| source.mass.0 / source.position.distance_squared(position);
+
+    candidates()
+        .filter_map(|source| {
+            let radius = source.sphere_of_influence?;
+            (source.position.distance(position) <= radius).then_some((source, radius))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(source, _)| source)
+        .or_else(|| {
+            candidates()
+                .filter(|source| source.sphere_of_influence.is_none())
+                .max_by(|a, b| pull(a).total_cmp(&pull(b)))
+        })
+        .or_else(|| candidates().max_by(|a, b| pull(a).total_cmp(&pull(b))))
+}
+
+/// Radius of the sphere of influence (Laplace) of a body with `mass` orbiting a body with `reference_mass`
+pub fn calc_sphere_of_influence(semi_major_axis: f32, mass: &Mass, reference_mass: &Mass) -> f32 {
+    semi_major_axis * (mass.0 / reference_mass.0).powf(0.4)
+}
+
+/// Update the sphere of influence of attractors that orbit other attractors
+pub(super) fn update_spheres_of_influence(
+    masses: Query<&Mass, With<Attractor>>,
+    mut query: Query<(&Mass, &OrbitalElements, &mut SphereOfInfluence)>,
+) {
+    query.iter_mut().for_each(|(i_mass, i_elements, mut i_sphere)| {
+        let Some(reference_mass) = i_elements.reference.and_then(|reference| masses.get(reference).ok()) else {
+            return;
+        };
+        if !i_elements.bound {
+            // without a closed orbit the sphere of influence is not well-defined, keep the last one
+            return;
+        }
+        i_sphere.radius = calc_sphere_of_influence(i_elements.semi_major_axis, i_mass, reference_mass);
+    });
+}
+
+pub fn calc_gravity_force(attractor_mass: &Mass, pos_attractor: Vec2, attractee_mass: &Mass, pos_attractee: Vec2) -> Vec2 {
     let distance = pos_attractor.distance(pos_attractee);
+    if distance <= f32::EPSILON {
+        // both bodies are in the same place so there is no meaningful direction to pull in
+        return Vec2::ZERO;
+    }
     let f = calc_gravity_force_magnitude(attractor_mass.0, attractee_mass.0, distance);
 
     (pos_attractor - pos_attractee).clamp_length(f, f)
 }
 
 fn calc_gravity_force_magnitude(m1: f32, m2: f32, r: f32) -> f32 {
-    #[allow(non_snake_case)]
-    let G: f32 = 6.674 * (10.0f32.powi(-11));
-    
-    G * ((m1 * m2) / r.powi(2))
+    GRAVITATIONAL_CONSTANT * ((m1 * m2) / r.powi(2))
+}
+
+pub(super) fn draw_spheres_of_influence(mut gizmos: Gizmos, query: Query<(&SphereOfInfluence, &Transform)>) {
+    query.iter().for_each(|(i_sphere, i_trans)| {
+        let color = Color::srgba_u8(0, 100, 255, 80);
+        gizmos.circle_2d(Isometry2d::from_translation(i_trans.translation.xy()), i_sphere.radius, color);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A heavy sun at the origin and a light planet orbiting it with a sphere of influence of 20
+    fn sources() -> [GravitySource; 2] {
+        [
+            GravitySource {
+                entity: Entity::from_raw_u32(0).unwrap(),
+                mass: Mass(1e14),
+                position: Vec2::ZERO,
+                velocity: None,
+                sphere_of_influence: None,
+            },
+            GravitySource {
+                entity: Entity::from_raw_u32(1).unwrap(),
+                mass: Mass(2e12),
+                position: Vec2::new(100.0, 0.0),
+                velocity: Some(Vec2::new(0.0, 8.0)),
+                sphere_of_influence: Some(20.0),
+            },
+        ]
+    }
+
+    #[test]
+    fn n_body_gravity_sums_the_pull_of_all_attractors() {
+        let [sun, planet] = sources();
+        let position = Vec2::new(110.0, 0.0);
+
+        let force = GravityModel::NBody.force(&sources(), None, &Mass(1.0), position);
+
+        let expected = calc_gravity_force(&sun.mass, sun.position, &Mass(1.0), position)
+            + calc_gravity_force(&planet.mass, planet.position, &Mass(1.0), position);
+        assert!(force.distance(expected) < 1e-4 * expected.length(), "{force} instead of {expected}");
+        // the sun is farther away but so much heavier that it still pulls harder
+        assert!(force.x < 0.0);
+    }
+
+    #[test]
+    fn patched_conics_only_feel_the_orbited_body_relative_to_its_parent() {
+        let [sun, planet] = sources();
+        let position = Vec2::new(110.0, 0.0);
+
+        let force = GravityModel::PatchedConics.force(&sources(), None, &Mass(1.0), position);
+
+        // the pull of the planet plus whatever pulls the planet, so that the orbit around it is undisturbed
+        let planet_acceleration = calc_gravity_force(&sun.mass, sun.position, &planet.mass, planet.position) / planet.mass.0;
+        let expected = calc_gravity_force(&planet.mass, planet.position, &Mass(1.0), position) + planet_acceleration;
+        assert!(force.distance(expected) < 1e-4 * expected.length(), "{force} instead of {expected}");
+        assert_eq!(dominant_body(&sources(), None, position).map(|body| body.entity), Some(planet.entity));
+    }
+
+    #[test]
+    fn both_models_agree_outside_of_spheres_of_influence_of_massless_planets() {
+        let mut sources = sources();
+        sources[1].mass = Mass(0.0);
+        let position = Vec2::new(-50.0, 30.0);
+
+        let n_body = GravityModel::NBody.force(&sources, None, &Mass(1.0), position);
+        let patched = GravityModel::PatchedConics.force(&sources, None, &Mass(1.0), position);
+        assert!(n_body.distance(patched) < 1e-4 * n_body.length(), "{n_body} and {patched} differ");
+    }
 }
@@ -0,0 +1,252 @@
+use crate::physics::calc_gravity::{
+    Attractee, Attractor, GravityModel, GravitySource, GravitySourceData, collect_gravity_sources,
+};
+use crate::physics::directional_forces::{Mass, ThrustForce};
+use crate::physics::velocity::{Velocity, calc_position_change};
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// The numerical method used to advance bodies through time.
+///
+/// The same integrator is used by the physics simulation and the orbit projections so that
+/// the drawn orbit matches the one a body will actually fly.
+#[derive(Resource, Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum Integrator {
+    /// First order, cheap but slowly gains or loses energy
+    SemiImplicitEuler,
+    /// Second order and symplectic (kick-drift-kick leapfrog), energy error stays bounded over long runs
+    #[default]
+    VelocityVerlet,
+    /// Fourth order Runge-Kutta, very accurate per step but not symplectic
+    Rk4,
+}
+
+impl Integrator {
+    /// Advance `position` and `velocity` by `dt` seconds.
+    /// `acceleration` is evaluated at intermediate positions as required by the chosen method.
+    pub fn step(&self, position: Vec2, velocity: Vec2, dt: f32, acceleration: impl Fn(Vec2) -> Vec2) -> (Vec2, Vec2) {
+        match self {
+            Integrator::SemiImplicitEuler => {
+                let velocity = velocity + acceleration(position) * dt;
+                (position + velocity * dt, velocity)
+            }
+            Integrator::VelocityVerlet => {
+                let half_velocity = velocity + acceleration(position) * (dt * 0.5);
+                let position = position + half_velocity * dt;
+                (position, half_velocity + acceleration(position) * (dt * 0.5))
+            }
+            Integrator::Rk4 => {
+                let k1_x = velocity;
+                let k1_v = acceleration(position);
+                let k2_x = velocity + k1_v * (dt * 0.5);
+                let k2_v = acceleration(position + k1_x * (dt * 0.5));
+                let k3_x = velocity + k2_v * (dt * 0.5);
+                let k3_v = acceleration(position + k2_x * (dt * 0.5));
+                let k4_x = velocity + k3_v * dt;
+                let k4_v = acceleration(position + k3_x * dt);
+
+                (
+                    position + (k1_x + 2.0 * k2_x + 2.0 * k3_x + k4_x) * (dt / 6.0),
+                    velocity + (k1_v + 2.0 * k2_v + 2.0 * k3_v + k4_v) * (dt / 6.0),
+                )
+            }
+        }
+    }
+
+    /// Advance all moving `sources` by `dt` seconds, pulled by each others gravity.
+    /// Used to predict where attractors will be, e.g. for orbit projections.
+    pub fn step_sources(&self, gravity: &GravityModel, sources: &mut [GravitySource], dt: f32) {
+        let snapshot = sources.to_vec();
+        for source in sources.iter_mut() {
+            let Some(velocity) = source.velocity else {
+                continue;
+            };
+            let (position, velocity) = self.step(source.position, velocity, dt, |position| {
+                gravity.force(&snapshot, Some(source.entity), &source.mass, position) / source.mass.0
+            });
+            source.position = position;
+            source.velocity = Some(velocity);
+        }
+    }
+}
+
+/// How the simulated time of a frame is split into physics ticks.
+///
+/// Bevy runs as many fixed ticks per frame as the virtual time needs, so at high time warp a frame is simulated
+/// in many short ticks instead of one long one. To keep slow frames from piling up ever more ticks, the time that
+/// is simulated per frame is limited and everything beyond it is dropped.
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct SubstepConfig {
+    /// Length of a physics tick in seconds of simulated time
+    pub max_step: f32,
+    /// Maximum number of physics ticks per frame. Time beyond `max_step * max_substeps` is dropped.
+    pub max_substeps: u32,
+}
+
+impl Default for SubstepConfig {
+    fn default() -> Self {
+        Self {
+            max_step: 1.0 / 64.0,
+            // enough for the highest time speed at 60 frames per second
+            max_substeps: 32,
+        }
+    }
+}
+
+impl SubstepConfig {
+    /// Longest time in seconds that is simulated in a single frame
+    pub fn max_frame_time(&self) -> f32 {
+        self.max_step * self.max_substeps as f32
+    }
+}
+
+/// Triggered when a frame was too long to be simulated within the configured sub-steps
+#[derive(Event, Debug)]
+pub struct SimulationTimeDropped {
+    /// How much time in seconds was not simulated
+    pub dropped: f32,
+}
+
+/// Dropped time is summed up and logged at most this often in seconds, so that a slow machine doesn't log every frame
+const DROPPED_TIME_LOG_INTERVAL: f32 = 1.0;
+
+/// Time dropped since it was last logged and when that was in real seconds
+#[derive(Debug, Default)]
+pub(super) struct DroppedTimeLog {
+    dropped: f32,
+    logged_at: Option<f32>,
+}
+
+pub(super) fn log_dropped_simulation_time(event: On<SimulationTimeDropped>, real: Res<Time<Real>>, mut log: Local<DroppedTimeLog>) {
+    let now = real.elapsed_secs();
+    log.dropped += event.dropped;
+    if log.logged_at.is_some_and(|logged_at| now - logged_at < DROPPED_TIME_LOG_INTERVAL) {
+        return;
+    }
+    warn!("Physics could not keep up and dropped {:.3}s of simulation time", log.dropped);
+    *log = DroppedTimeLog {
+        dropped: 0.0,
+        logged_at: Some(now),
+    };
+}
+
+/// Limit the virtual time of the frame to what can be simulated in `max_substeps` ticks, before it is advanced.
+///
+/// Bevy clamps the real time of a frame before scaling it with the time speed, so the limit depends on the speed.
+pub(super) fn limit_frame_time(substeps: Res<SubstepConfig>, mut time: ResMut<Time<Virtual>>) {
+    let speed = time.relative_speed();
+    if speed <= 0.0 {
+        return;
+    }
+    let max_delta = Duration::from_secs_f32(substeps.max_frame_time() / speed);
+    if time.max_delta() != max_delta {
+        time.set_max_delta(max_delta);
+    }
+}
+
+/// Report the time that was lost this frame because the virtual time was clamped
+pub(super) fn detect_dropped_time(mut commands: Commands, real: Res<Time<Real>>, virtual_time: Res<Time<Virtual>>) {
+    let expected = real.delta_secs() * virtual_time.effective_speed();
+    let dropped = expected - virtual_time.delta_secs();
+    // allow for rounding when the real time is scaled
+    if dropped > 1e-4 {
+        commands.trigger(SimulationTimeDropped { dropped });
+    }
+}
+
+/// The components of a body moved by [`integrate_motion`]
+type MovingBodyData = (
+    Entity,
+    &'static mut Transform,
+    &'static mut Velocity,
+    Option<&'static Mass>,
+    Option<&'static ThrustForce>,
+    Has<Attractee>,
+);
+
+type AttractorQuery<'w, 's> = Query<'w, 's, GravitySourceData, With<Attractor>>;
+type MovingBodyQuery<'w, 's> = Query<'w, 's, MovingBodyData>;
+
+/// Move all bodies according to their velocity, the gravity of the attractors and their thrust.
+///
+/// Attractors are snapshotted at the start of the tick and moved along their velocity to the middle of it,
+/// so bodies orbiting a moving attractor (e.g. a collector around a planet) don't lag behind it.
+pub(crate) fn integrate_motion(
+    integrator: Res<Integrator>,
+    gravity: Res<GravityModel>,
+    mut bodies: ParamSet<(AttractorQuery, MovingBodyQuery)>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    let sources = collect_gravity_sources(bodies.p0().iter())
+        .iter()
+        .map(|source| source.extrapolate(dt / 2.0))
+        .collect::<Vec<_>>();
+
+    bodies.p1().iter_mut().for_each(|(i_entity, mut i_trans, mut i_velocity, i_mass, i_thrust, i_attractee)| {
+        let Some(i_mass) = i_mass else {
+            // bodies without mass are not affected by forces and just drift along
+            i_trans.translation += calc_position_change(&i_velocity, dt).extend(0.0);
+            return;
+        };
+
+        let thrust = i_thrust.map(|thrust| thrust.0).unwrap_or_default();
+        let (position, velocity) = integrator.step(i_trans.translation.xy(), i_velocity.0, dt, |position| {
+            let mut forces = thrust;
+            if i_attractee {
+                forces += gravity.force(&sources, Some(i_entity), i_mass, position);
+            }
+            forces / i_mass.0
+        });
+
+        i_trans.translation = position.extend(i_trans.translation.z);
+        i_velocity.0 = velocity;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Integrator;
+    use bevy::math::Vec2;
+
+    const MU: f32 = 1.0;
+
+    fn acceleration(position: Vec2) -> Vec2 {
+        -position * MU / position.length().powi(3)
+    }
+
+    fn specific_energy(position: Vec2, velocity: Vec2) -> f32 {
+        velocity.length_squared() / 2.0 - MU / position.length()
+    }
+
+    /// Integrate an eccentric orbit (e = 0.5) for `orbits` revolutions and return the largest relative energy error
+    fn max_energy_drift(integrator: Integrator, orbits: usize, dt: f32) -> f32 {
+        let mut position = Vec2::new(1.0, 0.0);
+        let mut velocity = Vec2::new(0.0, 1.5f32.sqrt());
+        let initial_energy = specific_energy(position, velocity);
+
+        // semi-major axis of 2 gives a period of 2π·2^1.5
+        let period = 2.0 * std::f32::consts::PI * 2.0f32.powf(1.5);
+        let steps = (orbits as f32 * period / dt) as usize;
+
+        let mut max_drift = 0.0f32;
+        for _ in 0..steps {
+            (position, velocity) = integrator.step(position, velocity, dt, acceleration);
+            let drift = ((specific_energy(position, velocity) - initial_energy) / initial_energy).abs();
+            max_drift = max_drift.max(drift);
+        }
+        max_drift
+    }
+
+    #[test]
+    fn velocity_verlet_energy_stays_bounded_over_thousands_of_orbits() {
+        let drift = max_energy_drift(Integrator::VelocityVerlet, 2000, 0.01);
+        assert!(drift < 1e-3, "energy drifted by {drift}");
+    }
+
+    #[test]
+    fn rk4_energy_stays_bounded_over_thousands_of_orbits() {
+        let drift = max_energy_drift(Integrator::Rk4, 2000, 0.01);
+        assert!(drift < 1e-3, "energy drifted by {drift}");
+    }
+}
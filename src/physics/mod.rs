@@ -1,27 +1,65 @@
-pub(crate) mod calc_gravity;
-pub(crate) mod directional_forces;
-pub(crate) mod velocity;
+pub mod calc_gravity;
+pub mod directional_forces;
+pub mod integrator;
+pub mod orbital_elements;
+pub mod velocity;
 
 use crate::dev_tools::is_debug_enabled;
+use crate::physics::calc_gravity::GravityModel;
 use crate::physics::directional_forces::draw_directional_forces;
+use crate::physics::integrator::{Integrator, SubstepConfig};
 use crate::physics::velocity::draw_velocities;
 use crate::{AppSystems, GameplaySystem, PausableSystems};
 use bevy::prelude::*;
+use bevy::time::TimeSystems;
 
-pub(super) fn plugin(app: &mut App) {
-    app.add_systems(
-        FixedUpdate,
-        (
-            calc_gravity::apply_gravity,
-            directional_forces::apply_directional_force,
-            velocity::apply_velocity,
-        )
-            .chain()
-            .in_set(PausableSystems)
-            .in_set(GameplaySystem)
-            .in_set(AppSystems::Physics),
-    );
+/// Simulates gravity and movement of all bodies.
+#[derive(Debug, Default)]
+pub struct PhysicsPlugin {
+    /// The integrator used by the simulation and the orbit projections
+    pub integrator: Integrator,
+    /// How the pulls of several attractors are combined, in the simulation and the projections
+    pub gravity: GravityModel,
+    /// How long physics ticks are and how many of them may run per frame, so that high time warp stays stable
+    pub substeps: SubstepConfig,
+}
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.integrator);
+        app.insert_resource(self.gravity);
+        app.insert_resource(self.substeps);
+        app.insert_resource(Time::<Fixed>::from_seconds(self.substeps.max_step as f64));
+        app.add_observer(integrator::log_dropped_simulation_time);
+
+        app.add_systems(PreUpdate, integrator::detect_dropped_time.in_set(GameplaySystem));
+        app.add_systems(First, integrator::limit_frame_time.before(TimeSystems));
+
+        app.add_systems(
+            FixedUpdate,
+            (
+                calc_gravity::apply_gravity,
+                integrator::integrate_motion,
+                orbital_elements::update_orbital_elements,
+                calc_gravity::update_spheres_of_influence,
+            )
+                .chain()
+                .in_set(PausableSystems)
+                .in_set(GameplaySystem)
+                .in_set(AppSystems::Physics),
+        );
+
+        app.add_systems(
+            FixedPostUpdate,
+            directional_forces::clear_forces
+                .in_set(AppSystems::Physics)
+                .in_set(GameplaySystem),
+        );
+    }
+}
 
+/// Debug visualisation of the forces acting on bodies and the spheres of influence
+pub(super) fn presentation_plugin(app: &mut App) {
     app.add_systems(
         FixedPostUpdate,
         (
@@ -29,7 +67,7 @@ pub(super) fn plugin(app: &mut App) {
                 .run_if(is_debug_enabled)
                 .before(directional_forces::clear_forces),
             draw_velocities.run_if(is_debug_enabled),
-            directional_forces::clear_forces.in_set(AppSystems::Physics),
+            calc_gravity::draw_spheres_of_influence.run_if(is_debug_enabled),
         )
             .in_set(GameplaySystem),
     );
@@ -0,0 +1,224 @@
+//! Description of an orbit in terms of its classical (two-body) orbital elements.
+//!
+//! Everything in here is a pure function of the relative position and velocity of a body
+//! to the body it orbits, so that HUD, score and navigation code can reason about orbits
+//! instead of raw positions.
+
+use crate::physics::calc_gravity::{
+    Attractee, Attractor, GRAVITATIONAL_CONSTANT, GravitySourceData, collect_gravity_sources, dominant_body,
+};
+use crate::physics::directional_forces::Mass;
+use crate::physics::velocity::Velocity;
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// Eccentricities below this are treated as circular, which makes the argument of periapsis meaningless
+const CIRCULAR_EPSILON: f32 = 1e-4;
+
+#[derive(Component, Debug, Copy, Clone, PartialEq, Default)]
+pub struct OrbitalElements {
+    /// The body that is orbited, `None` if there is nothing to orbit
+    pub reference: Option<Entity>,
+    /// Half of the longest diameter of the orbit. Negative for hyperbolic trajectories
+    pub semi_major_axis: f32,
+    /// 0 for circles, between 0 and 1 for ellipses, 1 for parabolas and above 1 for hyperbolas
+    pub eccentricity: f32,
+    /// Angle (in radians, counter-clockwise from +X) from the reference body to the periapsis
+    pub argument_of_periapsis: f32,
+    /// Angle (in radians, in direction of travel) from the periapsis to the current position
+    pub true_anomaly: f32,
+    /// Closest distance to the reference body
+    pub periapsis: f32,
+    /// Farthest distance to the reference body, infinite for unbound trajectories
+    pub apoapsis: f32,
+    /// Time in seconds for one full orbit, infinite for unbound trajectories
+    pub period: f32,
+    /// Kinetic plus potential energy per unit of mass. Negative for bound orbits
+    pub specific_energy: f32,
+    /// Angular momentum per unit of mass. Positive for counter-clockwise orbits
+    pub specific_angular_momentum: f32,
+    /// Whether the body will keep orbiting (`true`) or escape the reference body (`false`)
+    pub bound: bool,
+}
+
+impl OrbitalElements {
+    /// Seconds until the body reaches the given true anomaly, `None` for unbound trajectories
+    pub fn time_until(&self, true_anomaly: f32) -> Option<f32> {
+        if !self.bound || self.eccentricity >= 1.0 {
+            return None;
+        }
+        let mean_anomaly = |true_anomaly: f32| {
+            let eccentric_anomaly = 2.0
+                * ((1.0 - self.eccentricity).sqrt() * (true_anomaly / 2.0).sin())
+                    .atan2((1.0 + self.eccentricity).sqrt() * (true_anomaly / 2.0).cos());
+            eccentric_anomaly - self.eccentricity * eccentric_anomaly.sin()
+        };
+        let remaining = (mean_anomaly(true_anomaly) - mean_anomaly(self.true_anomaly)).rem_euclid(2.0 * PI);
+        Some(remaining / (2.0 * PI) * self.period)
+    }
+}
+
+/// The standard gravitational parameter μ = G·M of a body
+pub fn gravitational_parameter(mass: &Mass) -> f32 {
+    GRAVITATIONAL_CONSTANT * mass.0
+}
+
+/// Calculate the orbital elements of a body from its position and velocity relative to the body it orbits
+pub fn calc_orbital_elements(mu: f32, relative_position: Vec2, relative_velocity: Vec2) -> OrbitalElements {
+    let r = relative_position.length();
+    let v_squared = relative_velocity.length_squared();
+    if r <= f32::EPSILON || mu <= 0.0 {
+        return OrbitalElements::default();
+    }
+
+    let specific_energy = v_squared / 2.0 - mu / r;
+    let specific_angular_momentum = relative_position.perp_dot(relative_velocity);
+
+    let eccentricity_vector = ((v_squared - mu / r) * relative_position
+        - relative_position.dot(relative_velocity) * relative_velocity)
+        / mu;
+    let eccentricity = eccentricity_vector.length();
+    let argument_of_periapsis = if eccentricity < CIRCULAR_EPSILON {
+        0.0
+    } else {
+        eccentricity_vector.to_angle()
+    };
+    let true_anomaly = ((relative_position.to_angle() - argument_of_periapsis)
+        * specific_angular_momentum.signum())
+    .rem_euclid(2.0 * PI);
+
+    let bound = specific_energy < 0.0;
+    let semi_major_axis = if specific_energy.abs() <= f32::EPSILON {
+        f32::INFINITY
+    } else {
+        -mu / (2.0 * specific_energy)
+    };
+
+    // derived from the semi-latus rectum which stays well-defined for all conic sections
+    let periapsis = specific_angular_momentum.powi(2) / mu / (1.0 + eccentricity);
+    let apoapsis = if bound && eccentricity < 1.0 {
+        semi_major_axis * (1.0 + eccentricity)
+    } else {
+        f32::INFINITY
+    };
+    let period = if bound {
+        2.0 * PI * (semi_major_axis.powi(3) / mu).sqrt()
+    } else {
+        f32::INFINITY
+    };
+
+    OrbitalElements {
+        reference: None,
+        semi_major_axis,
+        eccentricity,
+        argument_of_periapsis,
+        true_anomaly,
+        periapsis,
+        apoapsis,
+        period,
+        specific_energy,
+        specific_angular_momentum,
+        bound,
+    }
+}
+
+/// Speed required for a circular orbit at distance `r`
+pub fn circular_speed(mu: f32, r: f32) -> f32 {
+    (mu / r).sqrt()
+}
+
+/// Minimum speed at distance `r` with which a body escapes instead of orbiting
+pub fn escape_speed(mu: f32, r: f32) -> f32 {
+    (2.0 * mu / r).sqrt()
+}
+
+pub(super) fn update_orbital_elements(
+    attractor: Query<GravitySourceData, With<Attractor>>,
+    mut attractee: Query<(Entity, &Transform, &Velocity, &mut OrbitalElements), With<Attractee>>,
+) {
+    let sources = collect_gravity_sources(attractor.iter());
+
+    attractee.iter_mut().for_each(|(i_entity, i_trans, i_velocity, mut i_elements)| {
+        let i_pos = i_trans.translation.xy();
+        let Some(reference) = dominant_body(&sources, Some(i_entity), i_pos) else {
+            *i_elements = OrbitalElements::default();
+            return;
+        };
+
+        *i_elements = OrbitalElements {
+            reference: Some(reference.entity),
+            ..calc_orbital_elements(
+                gravitational_parameter(&reference.mass),
+                i_pos - reference.position,
+                i_velocity.0 - reference.velocity.unwrap_or_default(),
+            )
+        };
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MU: f32 = 1000.0;
+
+    #[test]
+    fn circular_orbit() {
+        let elements = calc_orbital_elements(MU, Vec2::new(10.0, 0.0), Vec2::new(0.0, circular_speed(MU, 10.0)));
+
+        assert!(elements.eccentricity < 1e-4, "eccentricity {}", elements.eccentricity);
+        assert!((elements.semi_major_axis - 10.0).abs() < 1e-3);
+        assert!((elements.periapsis - 10.0).abs() < 1e-3);
+        assert!((elements.apoapsis - 10.0).abs() < 1e-3);
+        assert!((elements.period - 2.0 * PI * (10.0f32.powi(3) / MU).sqrt()).abs() < 1e-3);
+        assert!(elements.bound);
+        assert!(elements.specific_angular_momentum > 0.0);
+    }
+
+    #[test]
+    fn elliptical_orbit() {
+        // at periapsis, vis-viva gives the speed for an orbit reaching out to 30
+        let (periapsis, apoapsis) = (10.0, 30.0);
+        let semi_major_axis = (periapsis + apoapsis) / 2.0;
+        let speed = (MU * (2.0 / periapsis - 1.0 / semi_major_axis)).sqrt();
+        let elements = calc_orbital_elements(MU, Vec2::new(0.0, periapsis), Vec2::new(-speed, 0.0));
+
+        assert!((elements.semi_major_axis - semi_major_axis).abs() < 1e-2);
+        assert!((elements.eccentricity - 0.5).abs() < 1e-4);
+        assert!((elements.periapsis - periapsis).abs() < 1e-2);
+        assert!((elements.apoapsis - apoapsis).abs() < 1e-2);
+        assert!((elements.argument_of_periapsis - PI / 2.0).abs() < 1e-4);
+        assert!(elements.true_anomaly.min(2.0 * PI - elements.true_anomaly) < 1e-3);
+        assert!((elements.specific_energy + MU / (2.0 * semi_major_axis)).abs() < 1e-3);
+        assert!(elements.bound);
+    }
+
+    #[test]
+    fn hyperbolic_trajectory() {
+        let elements = calc_orbital_elements(MU, Vec2::new(10.0, 0.0), Vec2::new(0.0, 1.5 * escape_speed(MU, 10.0)));
+
+        assert!(!elements.bound);
+        assert!(elements.eccentricity > 1.0);
+        assert!(elements.semi_major_axis < 0.0);
+        assert!(elements.specific_energy > 0.0);
+        assert!((elements.periapsis - 10.0).abs() < 1e-2);
+        assert_eq!(elements.apoapsis, f32::INFINITY);
+        assert_eq!(elements.period, f32::INFINITY);
+        assert_eq!(elements.time_until(PI), None);
+    }
+
+    #[test]
+    fn circular_and_escape_speed_follow_vis_viva() {
+        for r in [5.0, 10.0, 250.0] {
+            // v² = μ(2/r - 1/a) with a = r for a circle and a → ∞ for escape
+            assert!((circular_speed(MU, r).powi(2) - MU / r).abs() < 1e-3);
+            assert!((escape_speed(MU, r).powi(2) - 2.0 * MU / r).abs() < 1e-3);
+            assert!((escape_speed(MU, r) - 2.0f32.sqrt() * circular_speed(MU, r)).abs() < 1e-4);
+
+            let escaping = calc_orbital_elements(MU, Vec2::new(r, 0.0), Vec2::new(0.0, escape_speed(MU, r) * 1.001));
+            let orbiting = calc_orbital_elements(MU, Vec2::new(r, 0.0), Vec2::new(0.0, escape_speed(MU, r) * 0.999));
+            assert!(!escaping.bound);
+            assert!(orbiting.bound);
+        }
+    }
+}
@@ -3,12 +3,6 @@ use bevy::prelude::*;
 #[derive(Component, Debug, Copy, Clone, PartialEq, Default)]
 pub struct Velocity(pub Vec2);
 
-pub(super) fn apply_velocity(mut query: Query<(&Velocity, &mut Transform)>, time: Res<Time>) {
-    query.iter_mut().for_each(|(i_velocity, mut i_trans)| {
-        i_trans.translation += calc_position_change(i_velocity, time.delta_secs()).extend(0.0);
-    });
-}
-
 pub fn calc_position_change(velocity: &Velocity, time_delta: f32) -> Vec2 {
     velocity.0 * time_delta
 }
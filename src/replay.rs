@@ -0,0 +1,359 @@
+//! Deterministic simulation support.
+//!
+//! All player inputs that influence the simulation are funneled through [`PendingInputs`] and applied
+//! at the start of a fixed gameplay tick. Because the simulation itself only advances in `FixedUpdate`
+//! and randomness comes from a seeded [`RandomSource`], a run can be recorded as its seed plus the
+//! inputs of each tick and then be played back to produce an identical outcome.
+//!
+//! - `--seed <n>` seeds the random source explicitly
+//! - `--record <file>` saves the run to a file when the game ends
+//! - `--replay <file>` plays back a previously recorded run and ignores player inputs
+
+use crate::screens::Screen;
+use crate::sun_system::autopilot::AutopilotCommand;
+use crate::sun_system::thruster::ThrusterDirection;
+use crate::{AppSystems, GameplaySystem, RandomSource};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Sets up the seeded random source, the simulation clock and recording or replaying of inputs.
+pub struct ReplayPlugin {
+    pub options: ReplayOptions,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let options = &self.options;
+        let (mode, log) = match &options.replay {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Could not read replay file {}: {e}", path.display()));
+                let log = InputLog::parse(&contents)
+                    .unwrap_or_else(|e| panic!("Replay file {} is invalid: {e}", path.display()));
+                info!("Replaying {} inputs from {} with seed {}", log.inputs.len(), path.display(), log.seed);
+                (ReplayMode::Replaying { cursor: 0 }, log)
+            }
+            None => {
+                let seed = options.seed.unwrap_or_else(|| {
+                    ChaCha8Rng::try_from_os_rng()
+                        .unwrap_or(ChaCha8Rng::seed_from_u64(42))
+                        .random()
+                });
+                let mode = match &options.record {
+                    Some(path) => ReplayMode::Recording(path.clone()),
+                    None => ReplayMode::Live,
+                };
+                (mode, InputLog { seed, inputs: Vec::new() })
+            }
+        };
+        info!("Simulation seed is {}", log.seed);
+
+        app.insert_resource(RandomSource(ChaCha8Rng::seed_from_u64(log.seed)));
+        app.insert_resource(mode);
+        app.insert_resource(log);
+        app.init_resource::<PendingInputs>();
+        app.init_resource::<SimulationClock>();
+
+        app.add_systems(
+            FixedUpdate,
+            dispatch_inputs
+                .in_set(AppSystems::RecordInput)
+                .in_set(GameplaySystem),
+        );
+        app.add_systems(OnEnter(Screen::Gameover), save_recording);
+        app.add_systems(Last, save_recording.run_if(on_message::<AppExit>));
+    }
+}
+
+/// Options controlling determinism and recording, usually given on the command line
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReplayOptions {
+    /// Explicit seed for the random source, a random one is picked if this is `None`
+    pub seed: Option<u64>,
+    /// File to save the recording to when the game ends
+    pub record: Option<PathBuf>,
+    /// File with a recording that should be played back
+    pub replay: Option<PathBuf>,
+}
+
+impl ReplayOptions {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => options.seed = args.next().and_then(|seed| seed.parse().ok()),
+                "--record" => options.record = args.next().map(PathBuf::from),
+                "--replay" => options.replay = args.next().map(PathBuf::from),
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+/// Everything a player can do that influences the simulation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PlayerInput {
+    /// Launch a new collector from the launch pad
+    Launch { direction: Vec2, force: f32 },
+    /// Select the collector with the given [`CollectorId`](crate::launching::CollectorId) for adjustments
+    Select { collector: u32 },
+    /// Fire the thruster of the selected collector in a direction at a throttle between 0 and 1 or turn it off with `None`
+    Thruster { direction: Option<ThrusterDirection>, throttle: f32 },
+    /// Change how fast virtual time runs
+    TimeSpeed { speed: f32 },
+    /// Plan a burn of the selected collector at the given simulation time, replacing any planned before
+    PlanManeuver { at: f32, prograde: f32, radial: f32 },
+    /// Remove the planned burn of the selected collector
+    CancelManeuver,
+    /// Let the autopilot fly the selected collector or turn it off with `None`
+    Autopilot { command: Option<AutopilotCommand> },
+}
+
+/// Triggered at the start of a fixed gameplay tick for every input that should be applied in it
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct ApplyPlayerInput(pub PlayerInput);
+
+/// Inputs that have been made by the player but not yet applied to the simulation
+#[derive(Resource, Debug, Default)]
+pub struct PendingInputs(Vec<PlayerInput>);
+
+impl PendingInputs {
+    pub fn push(&mut self, input: PlayerInput) {
+        self.0.push(input);
+    }
+}
+
+/// Counts the fixed ticks and simulated time since gameplay started.
+/// Unlike `Time` this does not include time spent outside of gameplay, e.g. while loading.
+#[derive(Resource, Debug, Default)]
+pub struct SimulationClock {
+    pub tick: u64,
+    pub elapsed: f32,
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub enum ReplayMode {
+    /// Player inputs are applied but not saved
+    Live,
+    /// Player inputs are applied and saved to the given file when the game ends
+    Recording(PathBuf),
+    /// Player inputs are ignored and recorded inputs are applied instead
+    Replaying { cursor: usize },
+}
+
+impl ReplayMode {
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, ReplayMode::Replaying { .. })
+    }
+}
+
+/// Run condition for systems that read player input
+pub fn accepts_player_input(mode: Res<ReplayMode>) -> bool {
+    !mode.is_replaying()
+}
+
+/// A recorded run, i.e. the seed and all inputs together with the tick they were applied in
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct InputLog {
+    pub seed: u64,
+    pub inputs: Vec<(u64, PlayerInput)>,
+}
+
+impl InputLog {
+    /// Serialize the log into a simple line based text format
+    pub fn to_text(&self) -> String {
+        let mut result = format!("seed {}\n", self.seed);
+        for (tick, input) in &self.inputs {
+            let _ = match input {
+                PlayerInput::Launch { direction, force } => {
+                    writeln!(result, "{tick} launch {} {} {force}", direction.x, direction.y)
+                }
+                PlayerInput::Select { collector } => writeln!(result, "{tick} select {collector}"),
+                PlayerInput::Thruster { direction: None, throttle } => writeln!(result, "{tick} thruster off {throttle}"),
+                PlayerInput::Thruster {
+                    direction: Some(direction),
+                    throttle,
+                } => writeln!(result, "{tick} thruster {} {throttle}", direction.name()),
+                PlayerInput::TimeSpeed { speed } => writeln!(result, "{tick} speed {speed}"),
+                PlayerInput::PlanManeuver { at, prograde, radial } => {
+                    writeln!(result, "{tick} maneuver {at} {prograde} {radial}")
+                }
+                PlayerInput::CancelManeuver => writeln!(result, "{tick} cancel-maneuver"),
+                PlayerInput::Autopilot { command } => writeln!(
+                    result,
+                    "{tick} autopilot {}",
+                    command.map_or("off".to_string(), |command| command.to_text())
+                ),
+            };
+        }
+        result
+    }
+
+    /// Parse a log previously created by [`InputLog::to_text`]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.trim().parse().ok())
+            .ok_or("first line must contain the seed")?;
+
+        let mut inputs = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let invalid = || format!("invalid input on line {}: {line}", i + 2);
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let number = |index: usize| parts.get(index).and_then(|part| part.parse::<f32>().ok());
+
+            let tick = parts.first().and_then(|tick| tick.parse().ok()).ok_or_else(invalid)?;
+            let input = match parts.get(1).copied() {
+                Some("launch") => PlayerInput::Launch {
+                    direction: Vec2::new(number(2).ok_or_else(invalid)?, number(3).ok_or_else(invalid)?),
+                    force: number(4).ok_or_else(invalid)?,
+                },
+                Some("select") => PlayerInput::Select {
+                    collector: parts.get(2).and_then(|id| id.parse().ok()).ok_or_else(invalid)?,
+                },
+                Some("thruster") => PlayerInput::Thruster {
+                    direction: match parts.get(2).copied() {
+                        Some("off") => None,
+                        Some(name) => Some(ThrusterDirection::from_name(name).ok_or_else(invalid)?),
+                        None => return Err(invalid()),
+                    },
+                    throttle: number(3).ok_or_else(invalid)?,
+                },
+                Some("speed") => PlayerInput::TimeSpeed {
+                    speed: number(2).ok_or_else(invalid)?,
+                },
+                Some("maneuver") => PlayerInput::PlanManeuver {
+                    at: number(2).ok_or_else(invalid)?,
+                    prograde: number(3).ok_or_else(invalid)?,
+                    radial: number(4).ok_or_else(invalid)?,
+                },
+                Some("cancel-maneuver") => PlayerInput::CancelManeuver,
+                Some("autopilot") => PlayerInput::Autopilot {
+                    command: match parts.get(2).copied() {
+                        Some("off") => None,
+                        _ => Some(AutopilotCommand::parse(&parts[2..]).ok_or_else(invalid)?),
+                    },
+                },
+                _ => return Err(invalid()),
+            };
+            inputs.push((tick, input));
+        }
+
+        Ok(Self { seed, inputs })
+    }
+}
+
+fn dispatch_inputs(
+    mut commands: Commands,
+    mut clock: ResMut<SimulationClock>,
+    mut pending: ResMut<PendingInputs>,
+    mut mode: ResMut<ReplayMode>,
+    mut log: ResMut<InputLog>,
+    time: Res<Time>,
+) {
+    clock.tick += 1;
+    clock.elapsed += time.delta_secs();
+
+    let inputs = match mode.as_mut() {
+        ReplayMode::Replaying { cursor } => {
+            pending.0.clear();
+            let start = *cursor;
+            while log.inputs.get(*cursor).is_some_and(|(tick, _)| *tick <= clock.tick) {
+                *cursor += 1;
+            }
+            log.inputs[start..*cursor].iter().map(|(_, input)| *input).collect()
+        }
+        ReplayMode::Live | ReplayMode::Recording(_) => {
+            let inputs = std::mem::take(&mut pending.0);
+            log.inputs.extend(inputs.iter().map(|input| (clock.tick, *input)));
+            inputs
+        }
+    };
+
+    for input in inputs {
+        commands.trigger(ApplyPlayerInput(input));
+    }
+}
+
+fn save_recording(mode: Res<ReplayMode>, log: Res<InputLog>) {
+    let ReplayMode::Recording(path) = mode.as_ref() else {
+        return;
+    };
+
+    match std::fs::write(path, log.to_text()) {
+        Ok(()) => info!("Saved recording with {} inputs to {}", log.inputs.len(), path.display()),
+        Err(e) => error!("Could not save recording to {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_input_survives_a_round_trip_through_text() {
+        let mut inputs = vec![
+            PlayerInput::Launch {
+                direction: Vec2::new(0.6, -0.8),
+                force: 1.0 / 3.0,
+            },
+            PlayerInput::Select { collector: 7 },
+            PlayerInput::Thruster {
+                direction: None,
+                throttle: 0.25,
+            },
+            PlayerInput::TimeSpeed { speed: 12.0 },
+            PlayerInput::PlanManeuver {
+                at: 123.456,
+                prograde: -0.1,
+                radial: 2.5e-3,
+            },
+            PlayerInput::CancelManeuver,
+            PlayerInput::Autopilot { command: None },
+            PlayerInput::Autopilot {
+                command: Some(AutopilotCommand::Circularize),
+            },
+            PlayerInput::Autopilot {
+                command: Some(AutopilotCommand::CircularizeAtApoapsis),
+            },
+            PlayerInput::Autopilot {
+                command: Some(AutopilotCommand::SetPeriapsis { radius: 42.5 }),
+            },
+            PlayerInput::Autopilot {
+                command: Some(AutopilotCommand::HohmannTransfer { radius: 180.0 }),
+            },
+        ];
+        for direction in [
+            ThrusterDirection::Prograde,
+            ThrusterDirection::Retrograde,
+            ThrusterDirection::RadialIn,
+            ThrusterDirection::RadialOut,
+        ] {
+            inputs.push(PlayerInput::Thruster {
+                direction: Some(direction),
+                throttle: 0.7,
+            });
+        }
+        let log = InputLog {
+            seed: u64::MAX,
+            inputs: inputs.into_iter().enumerate().map(|(tick, input)| (tick as u64 * 3, input)).collect(),
+        };
+
+        assert_eq!(InputLog::parse(&log.to_text()), Ok(log));
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        assert!(InputLog::parse("").is_err());
+        assert!(InputLog::parse("seed 1\n3 warp 2\n").is_err());
+        assert!(InputLog::parse("seed 1\n3 launch 1.0\n").is_err());
+        assert!(InputLog::parse("seed 1\nsoon speed 2\n").is_err());
+        assert!(InputLog::parse("seed 1\n3 thruster prograde\n").is_err());
+    }
+}
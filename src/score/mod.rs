@@ -1,12 +1,13 @@
 use bevy::ecs::relationship::Relationship;
 use bevy::prelude::*;
-use crate::GameplaySystem;
+use crate::{AppSystems, GameplaySystem};
+use crate::collision::damage::Health;
 use crate::launching::CollectorStats;
 use crate::sun_system::{Level, Satellite, Sun};
 use std::collections::VecDeque;
 
 pub(crate) fn plugin(app: &mut App) {
-    app.add_systems(Update, update_score.in_set(GameplaySystem));
+    app.add_systems(FixedUpdate, update_score.in_set(GameplaySystem).in_set(AppSystems::Update));
     app.insert_resource(Score::default());
 }
 
@@ -32,9 +33,17 @@ impl Default for Score {
 #[derive(Component)]
 pub struct EnergyRateLabel;
 
+type CollectingSatelliteData = (
+    Entity,
+    &'static Transform,
+    &'static mut CollectorStats,
+    &'static Level,
+    Option<&'static Health>,
+);
+
 fn update_score(
     mut score: ResMut<Score>,
-    mut satellite_query: Query<(Entity, &Transform, &mut CollectorStats, &Level), With<Satellite>>,
+    mut satellite_query: Query<CollectingSatelliteData, With<Satellite>>,
     sun_query: Query<&Transform, (With<Sun>, Without<Satellite>) >,
     mut label_query: Query<(&ChildOf, &mut Text2d), With<EnergyRateLabel>>,
 
@@ -46,12 +55,14 @@ fn update_score(
     let current_time = time.elapsed_secs();
     let mut instant_rate = 0.01;
 
-    for (entity, satellite_transform, mut collector_stats, level) in satellite_query.iter_mut() {
+    for (entity, satellite_transform, mut collector_stats, level, health) in satellite_query.iter_mut() {
         let distance = satellite_transform.translation.distance(sun_position);
         if distance > 0.0 {
             let mut individual_rate = 2.0 / distance;
             collector_stats.energy_rate = individual_rate;
             individual_rate = individual_rate*level.level*200.;
+            // damaged collectors collect less
+            individual_rate *= health.map_or(1.0, Health::fraction);
             instant_rate += individual_rate;
 
             for (parent, mut text) in label_query.iter_mut() {
@@ -5,8 +5,10 @@ use bevy::input::mouse::MouseWheel;
 use crate::sun_system::{init_sun_system, Satellite, Sun};
 use bevy::prelude::*;
 use bevy::time::common_conditions::paused;
-use crate::collision::FatalCollisionEvent;
+use crate::collision::CollisionStats;
 use crate::GameplaySystem;
+use crate::boundary::LostToSpace;
+use crate::replay::SimulationClock;
 use crate::score::Score;
 use crate::screens::Screen;
 use crate::sun_system::SolarSystemAssets;
@@ -21,6 +23,9 @@ pub struct GameEnd{
 pub(super) fn plugin(app: &mut App) {
     app.insert_resource(GameEnd{game_end_time:600.0, ktype: 0.0});
     app.add_systems(Update, enter_gameover_screen.run_if(in_state(Screen::Gameplay).and(is_gameover)));
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
     app.add_systems(OnEnter(Screen::Gameover), show_game_over);
 }
 
@@ -37,10 +42,10 @@ fn enter_gameover_screen(mut next_screen: ResMut<NextState<Screen>>) {
 
 
 fn is_gameover( score: Res<Score>,
-                      time: Res<Time>,
+                      clock: Res<SimulationClock>,
                       game_end: Res<GameEnd>) -> bool {
     // 400 Yottawatt are 4 x 10^26, Kardashev type two,2.0 energy threshold
-   if( time.elapsed_secs() - game_end.game_end_time > 0. || score.energy_rate >= 400.){
+   if( clock.elapsed - game_end.game_end_time > 0. || score.energy_rate >= 400.){
        return true;
    }
     return false;
@@ -52,6 +57,8 @@ struct GameOverPopup;
 
 fn show_game_over(mut commands: Commands, mut score: ResMut<Score>,
                   mut game_end: ResMut<GameEnd>,
+                  lost: Res<LostToSpace>,
+                  collisions: Res<CollisionStats>,
                   solar_system_assets: Res<SolarSystemAssets>) {
     if(score.energy_rate >= 400.){ score.energy_rate=400.;}
     //let toYotta: f64=(score.energy_rate/100.) as f64* 1e24_f64; // multiplied by yotta
@@ -84,7 +91,7 @@ fn show_game_over(mut commands: Commands, mut score: ResMut<Score>,
             (
                 Node {
                     width: Val::Px(400.0),
-                    height: Val::Px(380.0),
+                    height: Val::Px(500.0),
                     border: UiRect::all(Val::Px(2.0)),
                     flex_direction: FlexDirection::Column,
                     justify_content: JustifyContent::Center,
@@ -158,6 +165,36 @@ fn show_game_over(mut commands: Commands, mut score: ResMut<Score>,
                         TextColor(Color::xyz(0.4811, 0.3064, 0.0253)),
                         TextLayout::new_with_justify(text_center),
                     ),
+                    // Collectors that crashed
+                    (
+                        Text::new(format!("COLLECTORS DESTROYED\n{}", collisions.collectors_destroyed)),
+                        Node {
+                            margin: UiRect::bottom(Val::Px(20.0)),
+                            ..default()
+                        },
+                        TextFont {
+                            font: solar_system_assets.font.clone(),
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::xyz(0.4811, 0.3064, 0.0253)),
+                        TextLayout::new_with_justify(text_center),
+                    ),
+                    // Collectors that flew off
+                    (
+                        Text::new(format!("COLLECTORS LOST TO SPACE\n{}", lost.collectors)),
+                        Node {
+                            margin: UiRect::bottom(Val::Px(20.0)),
+                            ..default()
+                        },
+                        TextFont {
+                            font: solar_system_assets.font.clone(),
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::xyz(0.4811, 0.3064, 0.0253)),
+                        TextLayout::new_with_justify(text_center),
+                    ),
                     // Kardashev Scale
                     (
                         Text::new(format!("Kardashev \nTYPE {:.3}\n {} ", game_end.ktype,better_earth)),
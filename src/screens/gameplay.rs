@@ -2,10 +2,11 @@
 
 use bevy::input::common_conditions::input_just_pressed;
 use bevy::input::mouse::MouseWheel;
-use crate::sun_system::{init_sun_system};
 use bevy::prelude::*;
 use crate::GameplaySystem;
+use crate::replay::{accepts_player_input, ApplyPlayerInput, PendingInputs, PlayerInput};
 use crate::screens::Screen;
+use crate::sun_system::thruster::THROTTLE_MODIFIER_KEYS;
 
 #[derive(Component)]
 struct CameraZoom {
@@ -13,11 +14,14 @@ struct CameraZoom {
 }
 
 pub(super) fn plugin(app: &mut App) {
+    app.add_observer(apply_time_speed);
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
     app.add_systems(OnEnter(Screen::Gameplay), setup_scene);
-    app.add_systems(OnEnter(Screen::Gameplay), init_sun_system);
     app.add_systems(Update, camera_zoom.in_set(GameplaySystem));
-    app.add_systems(Update, change_time_speed::<2>.run_if(input_just_pressed(KeyCode::ArrowUp)));
-    app.add_systems(Update, change_time_speed::<-2>.run_if(input_just_pressed(KeyCode::ArrowDown)));
+    app.add_systems(Update, change_time_speed::<2>.run_if(input_just_pressed(KeyCode::ArrowUp)).run_if(accepts_player_input));
+    app.add_systems(Update, change_time_speed::<-2>.run_if(input_just_pressed(KeyCode::ArrowDown)).run_if(accepts_player_input));
 }
 
 fn setup_scene(mut commands: Commands) {
@@ -30,20 +34,34 @@ fn setup_scene(mut commands: Commands) {
 }
 
 
-fn change_time_speed<const DELTA: i8>(mut time: ResMut<Time<Virtual>>) {
+fn change_time_speed<const DELTA: i8>(time: Res<Time<Virtual>>, mut pending_inputs: ResMut<PendingInputs>) {
     let time_speed = (time.relative_speed() + DELTA as f32)
         .round()
         .clamp(1., 20.);
 
-    info!("Time speed changed to {}", time_speed);
+    pending_inputs.push(PlayerInput::TimeSpeed { speed: time_speed });
+}
+
+fn apply_time_speed(input: On<ApplyPlayerInput>, mut time: ResMut<Time<Virtual>>) {
+    let PlayerInput::TimeSpeed { speed } = input.0 else {
+        return;
+    };
+
+    info!("Time speed changed to {}", speed);
     // set the speed of the virtual time to speed it up or slow it down
-    time.set_relative_speed(time_speed);
+    time.set_relative_speed(speed);
 }
 
 fn camera_zoom(
     mut scroll_evr: MessageReader<MouseWheel>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
     mut query: Query<(&mut Transform, &mut CameraZoom), With<Camera>>,
 ) {
+    // the mouse wheel changes the throttle instead
+    if keyboard_input.any_pressed(THROTTLE_MODIFIER_KEYS) {
+        scroll_evr.clear();
+    }
+
     //stepped zoom with predefined levels
     let zoom_levels = [0.1, 0.15, 0.25, 0.5, 0.75];
 
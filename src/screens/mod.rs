@@ -2,7 +2,7 @@
 
 mod loading;
 mod gameplay;
-mod gameover;
+pub mod gameover;
 //mod splash;
 //mod title;
 
@@ -13,8 +13,15 @@ pub(super) fn plugin(app: &mut App) {
     app.init_state::<Screen>();
     app.add_plugins((
         gameplay::plugin,
-        loading::plugin,
         gameover::plugin,
+    ));
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_plugins((
+        gameplay::presentation_plugin,
+        loading::plugin,
+        gameover::presentation_plugin,
         //splash::plugin,
         //title::plugin,
     ));
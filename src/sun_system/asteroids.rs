@@ -1,26 +1,40 @@
 use crate::asset_tracking::LoadResource;
 use crate::dev_tools::is_debug_enabled;
+use crate::physics::calc_gravity::Attractee;
+use crate::physics::directional_forces::Mass;
+use crate::physics::orbital_elements::gravitational_parameter;
 use crate::physics::velocity::Velocity;
+use crate::replay::SimulationClock;
+use crate::score::Score;
+use crate::sun_system::navigation_instruments::{OrbitProjection, TemporaryProjection};
+use crate::sun_system::SUN_MASS;
 use crate::{AppSystems, GameplaySystem, RandomSource};
-use bevy::color::palettes::basic::GREEN;
+use bevy::color::palettes::basic::{GREEN, RED};
 use bevy::prelude::*;
+use bevy::transform::helper::TransformHelper;
 use rand::Rng;
 use std::f32::consts::PI;
 use std::ops::Range;
-use std::time::Duration;
 use crate::collision::HitBox;
-use crate::sun_system::Level;
+use crate::collision::layers::{CollisionLayer, CollisionMask};
+use crate::collision::shape::HitBoxShape;
 
 pub fn plugin(app: &mut App) {
-    app.load_resource::<AsteroidAssets>();
     app.init_resource::<AsteroidConfig>();
-    app.init_resource::<AsteroidTracker>();
+    app.init_resource::<AsteroidDirector>();
     app.add_systems(
-        Update,
-        (asteroid_spawning_system)
+        FixedUpdate,
+        (direct_asteroids, despawn_empty_swarms)
             .in_set(GameplaySystem)
             .in_set(AppSystems::Update),
     );
+    app.add_observer(fragment_asteroid);
+}
+
+pub fn presentation_plugin(app: &mut App) {
+    app.load_resource::<AsteroidAssets>();
+    app.add_observer(add_asteroid_sprite);
+    app.add_observer(show_swarm_path);
     app.add_systems(PostUpdate, (draw_swarm_debug, draw_asteroid_debug).run_if(is_debug_enabled));
 }
 
@@ -39,16 +53,32 @@ impl FromWorld for AsteroidAssets {
     }
 }
 
-#[derive(Resource, Debug, PartialEq)]
+#[derive(Resource, Debug, Clone, PartialEq)]
 pub struct AsteroidConfig {
-    /// chance (evaluated per frame) that an asteroid swarm will spawn, expressed as 1 / $this
-    pub spawn_chance: usize,
-    /// Minimum time between asteroid swarms in seconds
-    pub min_time_between: usize,
-    /// Minimum time that the game should be running before the first swarm appears
-    pub min_initial_wait: usize,
-    /// A range of how many asteroids should be spawned
+    /// Whether the director sends any asteroids at all
+    pub enabled: bool,
+    /// Waves that arrive at fixed times, after the last one the director makes up its own waves
+    pub waves: Vec<AsteroidWave>,
+    /// Seconds between two made up waves at the start of a match
+    pub max_wave_interval: f32,
+    /// Seconds between two made up waves at the highest difficulty
+    pub min_wave_interval: f32,
+    /// How many swarms a made up wave has at the highest difficulty
+    pub max_swarms_per_wave: usize,
+    /// A range of how many asteroids the swarms of a made up wave have at the start of a match
     asteroid_gen_range: Range<usize>,
+    /// How many asteroids are added to that range at the highest difficulty
+    extra_asteroids: usize,
+    /// Seconds of simulated time after which the highest difficulty is reached
+    pub ramp_duration: f32,
+    /// Energy rate of the player at which the highest difficulty is reached
+    pub ramp_energy_rate: f32,
+    /// Distance from the sun at which swarms appear, at the edge of the play area but inside the [`WorldBoundary`](crate::boundary::WorldBoundary)
+    pub spawn_distance: f32,
+    /// A range of how fast swarms are when they appear
+    speed_range: Range<usize>,
+    /// A range of how close to the sun swarms pass by
+    flyby_range: Range<usize>,
     /// Radius in which asteroids are clustered
     cluster_radius: f32,
     /// Minimum distance between asteroids in a cluster
@@ -60,10 +90,22 @@ pub struct AsteroidConfig {
 impl Default for AsteroidConfig {
     fn default() -> Self {
         Self {
-            spawn_chance: 500,
-            min_time_between: 60,
-            min_initial_wait: 45,
+            enabled: true,
+            waves: vec![
+                AsteroidWave { at: 45.0, swarms: 1, asteroids: 2..4 },
+                AsteroidWave { at: 110.0, swarms: 1, asteroids: 3..6 },
+                AsteroidWave { at: 170.0, swarms: 2, asteroids: 2..5 },
+            ],
+            max_wave_interval: 60.0,
+            min_wave_interval: 20.0,
+            max_swarms_per_wave: 3,
             asteroid_gen_range: 2..6,
+            extra_asteroids: 3,
+            ramp_duration: 600.0,
+            ramp_energy_rate: 400.0,
+            spawn_distance: 350.0,
+            speed_range: 7..16,
+            flyby_range: 30..150,
             cluster_radius: 10.0,
             min_distance: 10.0,
             max_attempts: 10,
@@ -71,25 +113,60 @@ impl Default for AsteroidConfig {
     }
 }
 
-/// Helper for tracking state between asteroid system executions
-#[derive(Resource, Debug, Eq, PartialEq)]
-struct AsteroidTracker {
-    start_timer: Timer,
-    spawn_backoff_timer: Timer,
+impl AsteroidConfig {
+    /// How hard the match is between 0 and 1, it gets harder over time and the more energy the player collects
+    pub fn difficulty(&self, elapsed: f32, score: &Score) -> f32 {
+        (elapsed / self.ramp_duration + score.energy_rate / self.ramp_energy_rate).clamp(0.0, 1.0)
+    }
+
+    /// Make up the wave that follows one which arrived at `last`
+    fn make_up_wave(&self, last: f32, difficulty: f32, random: &mut RandomSource) -> AsteroidWave {
+        let interval = self.max_wave_interval + (self.min_wave_interval - self.max_wave_interval) * difficulty;
+        let extra = (self.extra_asteroids as f32 * difficulty).round() as usize;
+        AsteroidWave {
+            // a bit of jitter so that waves don't arrive like clockwork
+            at: last + interval * random.random_range(0.8..1.2),
+            swarms: 1 + ((self.max_swarms_per_wave.max(1) - 1) as f32 * difficulty).round() as usize,
+            asteroids: self.asteroid_gen_range.start + extra..self.asteroid_gen_range.end + extra,
+        }
+    }
+}
+
+/// Swarms that arrive together
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsteroidWave {
+    /// Seconds of simulated time since the start of the match at which the wave arrives
+    pub at: f32,
+    /// How many swarms arrive
+    pub swarms: usize,
+    /// A range of how many asteroids each swarm has
+    pub asteroids: Range<usize>,
 }
 
-impl FromWorld for AsteroidTracker {
-    fn from_world(world: &mut World) -> Self {
-        let cfg = world.resource::<AsteroidConfig>();
-        Self {
-            start_timer: Timer::new(
-                Duration::from_secs(cfg.min_initial_wait as u64),
-                TimerMode::Once,
-            ),
-            spawn_backoff_timer: Timer::new(
-                Duration::from_secs(cfg.min_time_between as u64),
-                TimerMode::Once,
-            ),
+/// Decides when asteroids arrive and how many, first the configured waves and then ever harder made up ones
+#[derive(Resource, Debug, Default)]
+pub struct AsteroidDirector {
+    /// How many of the configured waves have been planned
+    planned: usize,
+    /// When the last wave arrived
+    last_at: f32,
+    /// The wave that arrives next, planned as soon as the one before arrived
+    next: Option<AsteroidWave>,
+}
+
+impl AsteroidDirector {
+    /// The wave that arrives next and in how many seconds, if one is planned
+    pub fn forecast(&self, now: f32) -> Option<(f32, &AsteroidWave)> {
+        self.next.as_ref().map(|wave| ((wave.at - now).max(0.0), wave))
+    }
+
+    fn plan(&mut self, cfg: &AsteroidConfig, difficulty: f32, random: &mut RandomSource) -> AsteroidWave {
+        match cfg.waves.get(self.planned) {
+            Some(wave) => {
+                self.planned += 1;
+                wave.clone()
+            }
+            None => cfg.make_up_wave(self.last_at, difficulty, random),
         }
     }
 }
@@ -100,67 +177,114 @@ impl FromWorld for AsteroidTracker {
 #[require(Transform)]
 pub struct AsteroidSwarm;
 
-/// Marker component to mark asteroids
+/// An asteroid, either part of a swarm or a fragment flying on its own
 #[derive(Component, Debug, Eq, PartialEq, Hash)]
-#[require(Transform, Sprite)]
-pub struct Asteroid;
+#[require(Transform)]
+pub struct Asteroid {
+    /// Asteroids larger than 1 break into two asteroids one size smaller when struck
+    pub size: u32,
+}
+
+impl Asteroid {
+    pub fn radius(&self) -> f32 {
+        ASTEROID_RADIUS * self.size as f32 / LARGE_ASTEROID_SIZE as f32
+    }
+
+    /// Heavier asteroids do more damage to what they hit
+    pub fn mass(&self) -> Mass {
+        Mass(self.size as f32)
+    }
+
+    /// Asteroids drift through each other and through debris
+    pub fn hitbox(&self) -> HitBox {
+        HitBox {
+            shape: HitBoxShape::Circle { radius: self.radius() },
+            layer: CollisionLayer::Asteroid,
+            mask: CollisionMask::ALL.without(CollisionLayer::Asteroid).without(CollisionLayer::Debris),
+        }
+    }
+
+    fn scale(&self) -> Vec3 {
+        Vec3::splat(0.01 * self.size as f32 / LARGE_ASTEROID_SIZE as f32)
+    }
+}
+
+/// Size of the asteroids a swarm spawns with
+pub const LARGE_ASTEROID_SIZE: u32 = 3;
+/// Hitbox radius of a large asteroid
+const ASTEROID_RADIUS: f32 = 5.0;
+/// How fast fragments drift apart from each other
+const FRAGMENT_SPEED: f32 = 2.0;
 
 #[derive(Event, Debug)]
-pub struct AsteroidSwarmSpawned;
+pub struct AsteroidSwarmSpawned {
+    pub swarm: Entity,
+}
 
-fn asteroid_spawning_system(
+/// Triggered when something other than a celestial body flies into an asteroid, right before the asteroid is destroyed
+#[derive(Event, Debug)]
+pub struct AsteroidStruck {
+    pub asteroid: Entity,
+    pub other: Entity,
+}
+
+/// How long the path of a new swarm is shown
+const SWARM_PATH_SECONDS: f32 = 10.0;
+
+/// Send the planned wave once it is due and plan the one after it right away, so that it can be forecast
+fn direct_asteroids(
     mut commands: Commands,
-    assets: Res<AsteroidAssets>,
     cfg: Res<AsteroidConfig>,
+    clock: Res<SimulationClock>,
+    score: Res<Score>,
     mut randomness: ResMut<RandomSource>,
-    mut tracker: ResMut<AsteroidTracker>,
-    time: Res<Time>,
+    mut director: ResMut<AsteroidDirector>,
 ) {
-    tracker.start_timer.tick(time.delta());
-    tracker.spawn_backoff_timer.tick(time.delta());
-
-    // don't execute the remaining system if gameplay has not been running for the configured amount of time
-    if !tracker.start_timer.is_finished() {
+    if !cfg.enabled {
         return;
     }
-    if tracker.start_timer.just_finished() {
-        info!("Grace period has expired and asteroids can spawn now");
-        tracker.spawn_backoff_timer.finish();
-    }
-
-    // don't try to spawn anything if we've just done so (the backoff timer is running)
-    if !tracker.spawn_backoff_timer.is_finished() {
+    let difficulty = cfg.difficulty(clock.elapsed, &score);
+    let wave = match director.next.take() {
+        Some(wave) => wave,
+        None => director.plan(&cfg, difficulty, &mut randomness),
+    };
+    if wave.at > clock.elapsed {
+        director.next = Some(wave);
         return;
     }
 
-    // if the backoff has been reached, spawn something if randomness lets us
-    if randomness.random_ratio(1, cfg.spawn_chance as u32) {
-        tracker.spawn_backoff_timer.reset();
-        let swarm = spawn_asteroids(&mut commands, &cfg, &assets, &mut randomness);
-        commands.trigger(AsteroidSwarmSpawned);
+    info!("Asteroid wave with {} swarms arrives at difficulty {difficulty:.2}", wave.swarms);
+    for _ in 0..wave.swarms {
+        let swarm = spawn_asteroids(&mut commands, &cfg, wave.asteroids.clone(), &mut randomness);
+        commands.trigger(AsteroidSwarmSpawned { swarm });
     }
+    director.last_at = wave.at;
+    director.next = Some(director.plan(&cfg, difficulty, &mut randomness));
 }
 
 fn spawn_asteroids(
     commands: &mut Commands,
     cfg: &AsteroidConfig,
-    assets: &AsteroidAssets,
+    asteroids: Range<usize>,
     random: &mut RandomSource,
 ) -> Entity {
-    let num_asteroids = random.random_range(cfg.asteroid_gen_range.clone());
-    let direction = random.random_range(-45..45) as f32 * PI / 180.0;
-    let speed = random.random_range(10..20) as f32;
+    let num_asteroids = random.random_range(asteroids);
+    // come in from any direction and pass the sun on either side
+    let position = Vec2::from_angle(random.random_range(0..360) as f32 * PI / 180.0) * cfg.spawn_distance;
+    let speed = random.random_range(cfg.speed_range.clone()) as f32;
+    let flyby = random.random_range(cfg.flyby_range.clone()) as f32;
+    let velocity = flyby_velocity(gravitational_parameter(&Mass(SUN_MASS)), position, speed, flyby, random.random_bool(0.5));
     info!("Spawning asteroid swarm with {num_asteroids} asteroids");
 
     let swarm = commands
         .spawn((
             AsteroidSwarm,
-            Level{level:-1.},
-            Transform::from_translation(Vec3::new(-50.0, -150.0, 0.0))
-                .with_rotation(Quat::from_axis_angle(Vec3::Z, direction)),
+            Attractee,
+            Mass(1.0),
+            Transform::from_translation(position.extend(0.0))
+                .with_rotation(Quat::from_axis_angle(Vec3::Z, velocity.to_angle() - 0.5 * PI)),
             InheritedVisibility::default(),
-            Velocity(Vec2::from_angle(direction + 0.5 * PI) * speed),
-            HitBox { radius: 14.0 },
+            Velocity(velocity),
         ))
         .id();
 
@@ -184,14 +308,15 @@ fn spawn_asteroids(
         if let Some(pos) = position {
             positions.push(pos);
 
+            let asteroid = Asteroid { size: LARGE_ASTEROID_SIZE };
             commands.spawn((
-                Asteroid,
+                asteroid.hitbox(),
+                asteroid.mass(),
                 ChildOf(swarm),
                 Transform::from_translation(Vec3::new(pos.x, pos.y, 0.0))
-                    .with_scale(Vec3::splat(0.01))
+                    .with_scale(asteroid.scale())
                     .with_rotation(Quat::from_axis_angle(Vec3::X, PI)),
-                Sprite::from(assets.asteroid.clone()),
-
+                asteroid,
             ));
         }
     }
@@ -199,6 +324,99 @@ fn spawn_asteroids(
     swarm
 }
 
+/// Velocity with which a body at `position` and with `speed` passes the sun at a distance of `periapsis`.
+/// Fast bodies fly by on a hyperbola, slow ones come back on an ellipse.
+fn flyby_velocity(mu: f32, position: Vec2, speed: f32, periapsis: f32, clockwise: bool) -> Vec2 {
+    let distance = position.length();
+    // energy and angular momentum stay the same along the trajectory
+    let specific_energy = speed * speed / 2.0 - mu / distance;
+    let periapsis_speed = (2.0 * (specific_energy + mu / periapsis)).max(0.0).sqrt();
+    let tangential = (periapsis * periapsis_speed / distance).min(speed);
+    let radial = (speed * speed - tangential * tangential).sqrt();
+
+    let inwards = -position / distance;
+    let sideways = if clockwise { -inwards.perp() } else { inwards.perp() };
+    inwards * radial + sideways * tangential
+}
+
+/// An asteroid that flies on its own instead of along with a swarm
+pub fn free_asteroid(size: u32, position: Vec2, rotation: Quat, velocity: Vec2) -> impl Bundle {
+    let asteroid = Asteroid { size };
+    (
+        Attractee,
+        asteroid.mass(),
+        asteroid.hitbox(),
+        Transform::from_translation(position.extend(0.0))
+            .with_scale(asteroid.scale())
+            .with_rotation(rotation),
+        Velocity(velocity),
+        asteroid,
+    )
+}
+
+/// Break a large asteroid that was struck into two smaller ones which keep its velocity and drift apart sideways
+fn fragment_asteroid(
+    event: On<AsteroidStruck>,
+    mut commands: Commands,
+    helper: TransformHelper,
+    asteroids: Query<(&Asteroid, Option<&ChildOf>)>,
+    velocities: Query<&Velocity>,
+    hitboxes: Query<&HitBox>,
+) {
+    let Ok((asteroid, parent)) = asteroids.get(event.asteroid) else {
+        return;
+    };
+    if asteroid.size <= 1 {
+        return;
+    }
+    let (Ok(global), Ok(other)) = (
+        helper.compute_global_transform(event.asteroid),
+        helper.compute_global_transform(event.other),
+    ) else {
+        return;
+    };
+    let velocity = velocities
+        .get(event.asteroid)
+        .ok()
+        .or_else(|| velocities.get(parent?.parent()).ok())
+        .map_or(Vec2::ZERO, |velocity| velocity.0);
+
+    let position = global.translation().xy();
+    let sideways = (position - other.translation().xy()).normalize_or(Vec2::X).perp();
+    let size = asteroid.size - 1;
+    // far enough to the sides that whatever struck the asteroid passes between the fragments
+    let offset = Asteroid { size }.radius() + hitboxes.get(event.other).map_or(0.0, |hitbox| hitbox.shape.bounding_radius());
+    for side in [-1.0, 1.0] {
+        commands.spawn(free_asteroid(
+            size,
+            position + sideways * side * offset,
+            global.rotation(),
+            velocity + sideways * side * FRAGMENT_SPEED,
+        ));
+    }
+}
+
+/// Swarms whose asteroids have all been destroyed are removed
+fn despawn_empty_swarms(mut commands: Commands, swarms: Query<Entity, (With<AsteroidSwarm>, Without<Children>)>) {
+    for swarm in &swarms {
+        commands.entity(swarm).despawn();
+    }
+}
+
+/// Show where a new swarm is going to fly while the debris warning is shown
+pub fn show_swarm_path(event: On<AsteroidSwarmSpawned>, mut commands: Commands) {
+    commands.entity(event.swarm).insert((
+        OrbitProjection::default(),
+        TemporaryProjection::new(SWARM_PATH_SECONDS, RED),
+    ));
+}
+
+fn add_asteroid_sprite(event: On<Add, Asteroid>, mut commands: Commands, assets: Res<AsteroidAssets>) {
+    commands
+        .entity(event.entity)
+        .insert(Sprite::from(assets.asteroid.clone()));
+}
+
 fn draw_swarm_debug(mut gizmos: Gizmos, query: Query<&GlobalTransform, With<AsteroidSwarm>>) {
     query.iter().for_each(|(i_trans)| {
         let isometry = Isometry2d::from_translation(i_trans.translation().xy());
@@ -0,0 +1,473 @@
+//! The autopilot shapes the orbit of the selected collector by planning [`ManeuverNode`]s for it,
+//! one burn after the other, and reports an [`AutopilotFailed`] when the burns can't be done.
+//!
+//! Press C to circularize right away, V to circularize at the apoapsis, P to move the periapsis
+//! to the distance of the mouse cursor, H for a transfer to a circular orbit at that distance
+//! and X to turn the autopilot off.
+
+use crate::collision::HitBox;
+use crate::launching::Fuel;
+use crate::physics::calc_gravity::{Attractor, GravitySourceData, collect_gravity_sources};
+use crate::physics::directional_forces::Mass;
+use crate::physics::orbital_elements::{OrbitalElements, circular_speed, gravitational_parameter};
+use crate::physics::velocity::Velocity;
+use crate::replay::{ApplyPlayerInput, PendingInputs, PlayerInput, SimulationClock, accepts_player_input};
+use crate::sun_system::maneuver::{ManeuverFinished, ManeuverNode, execute_maneuver_nodes, orbit_frame};
+use crate::sun_system::navigation_instruments::NavigationInstruments;
+use crate::sun_system::thruster::Thruster;
+use crate::{AppSystems, GameplaySystem};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::fmt;
+
+/// Orbits less eccentric than this are treated as circular, so burns at an apsis are done right away
+const CIRCULAR_ENOUGH: f32 = 0.01;
+/// Burns smaller than this are skipped
+const MIN_DELTA_V: f32 = 0.001;
+
+const AUTOPILOT_KEYS: [(KeyCode, AutopilotKey); 5] = [
+    (KeyCode::KeyC, AutopilotKey::Circularize),
+    (KeyCode::KeyV, AutopilotKey::CircularizeAtApoapsis),
+    (KeyCode::KeyP, AutopilotKey::SetPeriapsis),
+    (KeyCode::KeyH, AutopilotKey::HohmannTransfer),
+    (KeyCode::KeyX, AutopilotKey::Off),
+];
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_observer(apply_autopilot_input);
+    app.add_observer(continue_after_maneuver);
+    app.add_systems(
+        FixedUpdate,
+        run_autopilot
+            .in_set(GameplaySystem)
+            .in_set(AppSystems::Physics)
+            .before(execute_maneuver_nodes),
+    );
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        record_autopilot_input
+            .run_if(accepts_player_input)
+            .in_set(GameplaySystem)
+            .in_set(AppSystems::RecordInput),
+    );
+}
+
+/// What the autopilot should do with the orbit
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AutopilotCommand {
+    /// Make the orbit circular at the current distance to the orbited body
+    Circularize,
+    /// Make the orbit circular at its farthest point
+    CircularizeAtApoapsis,
+    /// Raise or lower the closest point of the orbit to `radius` by burning at the farthest point
+    SetPeriapsis { radius: f32 },
+    /// Get to a circular orbit at `radius` with two burns
+    HohmannTransfer { radius: f32 },
+}
+
+impl AutopilotCommand {
+    /// The burns needed for the command, they are planned one after the other
+    fn steps(&self, elements: &OrbitalElements) -> VecDeque<Step> {
+        match *self {
+            AutopilotCommand::Circularize => [Step::CircularizeNow].into(),
+            AutopilotCommand::CircularizeAtApoapsis => [Step::Circularize(Apsis::Apoapsis)].into(),
+            AutopilotCommand::SetPeriapsis { radius } => [Step::MoveOppositeApsis(Apsis::Apoapsis, radius)].into(),
+            // raise the apoapsis from the periapsis or lower the periapsis from the apoapsis,
+            // then circularize once the target radius is reached
+            AutopilotCommand::HohmannTransfer { radius } if radius >= elements.semi_major_axis => [
+                Step::MoveOppositeApsis(Apsis::Periapsis, radius),
+                Step::Circularize(Apsis::Apoapsis),
+            ]
+            .into(),
+            AutopilotCommand::HohmannTransfer { radius } => [
+                Step::MoveOppositeApsis(Apsis::Apoapsis, radius),
+                Step::Circularize(Apsis::Periapsis),
+            ]
+            .into(),
+        }
+    }
+
+    /// The radius the command aims for, if any
+    fn target_radius(&self) -> Option<f32> {
+        match *self {
+            AutopilotCommand::SetPeriapsis { radius } | AutopilotCommand::HohmannTransfer { radius } => Some(radius),
+            _ => None,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            AutopilotCommand::Circularize => "circularize".to_string(),
+            AutopilotCommand::CircularizeAtApoapsis => "circularize-apoapsis".to_string(),
+            AutopilotCommand::SetPeriapsis { radius } => format!("periapsis {radius}"),
+            AutopilotCommand::HohmannTransfer { radius } => format!("hohmann {radius}"),
+        }
+    }
+
+    /// Parse the words of a command previously created by [`AutopilotCommand::to_text`]
+    pub fn parse(words: &[&str]) -> Option<Self> {
+        let radius = || words.get(1).and_then(|radius| radius.parse().ok());
+        match words.first().copied()? {
+            "circularize" => Some(AutopilotCommand::Circularize),
+            "circularize-apoapsis" => Some(AutopilotCommand::CircularizeAtApoapsis),
+            "periapsis" => Some(AutopilotCommand::SetPeriapsis { radius: radius()? }),
+            "hohmann" => Some(AutopilotCommand::HohmannTransfer { radius: radius()? }),
+            _ => None,
+        }
+    }
+}
+
+/// Flies a collector according to an [`AutopilotCommand`]
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Autopilot {
+    pub command: AutopilotCommand,
+    /// Burns that still have to be planned, `None` until the command is started
+    steps: Option<VecDeque<Step>>,
+}
+
+impl Autopilot {
+    pub fn new(command: AutopilotCommand) -> Self {
+        Self { command, steps: None }
+    }
+}
+
+/// Why the autopilot gave up on a command
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AutopilotError {
+    /// The collector isn't orbiting anything
+    NoOrbit,
+    /// The burn has to happen at an apsis but the collector is escaping and never gets there
+    Unbound,
+    /// The target radius is inside the orbited body or outside of its sphere of influence
+    InvalidRadius { radius: f32 },
+    /// There isn't enough fuel left for the burns
+    InsufficientFuel { needed: f32, available: f32 },
+}
+
+impl fmt::Display for AutopilotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutopilotError::NoOrbit => write!(f, "not orbiting anything"),
+            AutopilotError::Unbound => write!(f, "not on a closed orbit"),
+            AutopilotError::InvalidRadius { radius } => write!(f, "can't orbit at radius {radius:.0}"),
+            AutopilotError::InsufficientFuel { needed, available } => {
+                write!(f, "needs {needed:.2} delta-v but only {available:.2} is left")
+            }
+        }
+    }
+}
+
+/// Triggered when the autopilot gives up on its command
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct AutopilotFailed {
+    pub collector: Entity,
+    pub command: AutopilotCommand,
+    pub error: AutopilotError,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Apsis {
+    Periapsis,
+    Apoapsis,
+}
+
+impl Apsis {
+    fn true_anomaly(&self) -> f32 {
+        match self {
+            Apsis::Periapsis => 0.0,
+            Apsis::Apoapsis => PI,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Step {
+    /// Circularize where the collector is right now
+    CircularizeNow,
+    /// Circularize once the collector reaches the apsis
+    Circularize(Apsis),
+    /// Burn at the apsis so that the opposite apsis ends up at the given radius
+    MoveOppositeApsis(Apsis, f32),
+}
+
+/// A burn planned by the autopilot
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct PlannedBurn {
+    /// Seconds until the burn
+    in_seconds: f32,
+    /// Change of velocity, `x` along prograde and `y` along radial-out
+    delta_v: Vec2,
+}
+
+/// Periapsis and apoapsis of an orbit, nearly circular orbits are treated as circles at the current radius
+fn apsides(elements: &OrbitalElements, radius: f32) -> (f32, f32) {
+    if elements.eccentricity < CIRCULAR_ENOUGH {
+        (radius, radius)
+    } else {
+        (elements.periapsis, elements.apoapsis)
+    }
+}
+
+/// Speed at `radius` on an orbit with the given periapsis and apoapsis (vis-viva equation)
+fn orbit_speed(mu: f32, radius: f32, periapsis: f32, apoapsis: f32) -> f32 {
+    (mu * (2.0 / radius - 2.0 / (periapsis + apoapsis))).max(0.0).sqrt()
+}
+
+/// Radius of the apsis and the apsides of the orbit after burning there, for an orbit with the given apsides
+fn burn_at_apsis(step: Step, mu: f32, (periapsis, apoapsis): (f32, f32)) -> (f32, f32, (f32, f32)) {
+    let radius_at = |apsis| match apsis {
+        Apsis::Periapsis => periapsis,
+        Apsis::Apoapsis => apoapsis,
+    };
+    let (radius, new_apsides) = match step {
+        Step::CircularizeNow => unreachable!("circularizing right away does not wait for an apsis"),
+        Step::Circularize(apsis) => (radius_at(apsis), (radius_at(apsis), radius_at(apsis))),
+        Step::MoveOppositeApsis(apsis, target) => (radius_at(apsis), (radius_at(apsis).min(target), radius_at(apsis).max(target))),
+    };
+    let delta_v = orbit_speed(mu, radius, new_apsides.0, new_apsides.1) - orbit_speed(mu, radius, periapsis, apoapsis);
+    (radius, delta_v, new_apsides)
+}
+
+/// Plan the burn of the next step based on the current orbit
+fn plan_step(
+    step: Step,
+    mu: f32,
+    elements: &OrbitalElements,
+    relative_position: Vec2,
+    relative_velocity: Vec2,
+) -> Result<PlannedBurn, AutopilotError> {
+    let radius = relative_position.length();
+
+    if step == Step::CircularizeNow {
+        let (prograde, radial_out) = orbit_frame(relative_position, relative_velocity).ok_or(AutopilotError::NoOrbit)?;
+        let direction = relative_position.normalize().perp() * elements.specific_angular_momentum.signum();
+        let delta_v = direction * circular_speed(mu, radius) - relative_velocity;
+        return Ok(PlannedBurn {
+            in_seconds: 0.0,
+            delta_v: Vec2::new(delta_v.dot(prograde), delta_v.dot(radial_out)),
+        });
+    }
+
+    if !elements.bound {
+        return Err(AutopilotError::Unbound);
+    }
+    let (_, delta_v, _) = burn_at_apsis(step, mu, apsides(elements, radius));
+    let in_seconds = match step {
+        _ if elements.eccentricity < CIRCULAR_ENOUGH => 0.0,
+        Step::Circularize(apsis) | Step::MoveOppositeApsis(apsis, _) => {
+            elements.time_until(apsis.true_anomaly()).ok_or(AutopilotError::Unbound)?
+        }
+        Step::CircularizeNow => 0.0,
+    };
+    Ok(PlannedBurn {
+        in_seconds,
+        delta_v: Vec2::new(delta_v, 0.0),
+    })
+}
+
+/// Total delta-v of the remaining steps, assuming that every burn is done perfectly
+fn estimate_delta_v(
+    steps: &VecDeque<Step>,
+    mu: f32,
+    elements: &OrbitalElements,
+    relative_position: Vec2,
+    relative_velocity: Vec2,
+) -> Result<f32, AutopilotError> {
+    let radius = relative_position.length();
+    let mut apsides = apsides(elements, radius);
+    let mut total = 0.0;
+    for (i, step) in steps.iter().enumerate() {
+        if *step == Step::CircularizeNow {
+            total += plan_step(*step, mu, elements, relative_position, relative_velocity)?.delta_v.length();
+            apsides = (radius, radius);
+        } else if i == 0 && !elements.bound {
+            return Err(AutopilotError::Unbound);
+        } else {
+            let (_, delta_v, new_apsides) = burn_at_apsis(*step, mu, apsides);
+            total += delta_v.abs();
+            apsides = new_apsides;
+        }
+    }
+    Ok(total)
+}
+
+fn apply_autopilot_input(
+    input: On<ApplyPlayerInput>,
+    mut commands: Commands,
+    query: Query<Entity, With<NavigationInstruments>>,
+) {
+    for entity in query.iter() {
+        match input.0 {
+            PlayerInput::Autopilot { command: Some(command) } => {
+                commands.entity(entity).remove::<ManeuverNode>().insert(Autopilot::new(command));
+            }
+            PlayerInput::Autopilot { command: None } => {
+                commands.entity(entity).remove::<(Autopilot, ManeuverNode)>();
+            }
+            // the player takes over
+            PlayerInput::PlanManeuver { .. } | PlayerInput::CancelManeuver => {
+                commands.entity(entity).remove::<Autopilot>();
+            }
+            _ => {}
+        }
+    }
+}
+
+type AutopilotCollectorData = (
+    Entity,
+    &'static mut Autopilot,
+    &'static OrbitalElements,
+    &'static Transform,
+    &'static Velocity,
+    &'static Fuel,
+    &'static Thruster,
+    &'static Mass,
+);
+
+/// Plan the next burn whenever the previous one is done
+fn run_autopilot(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    attractor: Query<GravitySourceData, With<Attractor>>,
+    hitboxes: Query<&HitBox>,
+    mut query: Query<AutopilotCollectorData, Without<ManeuverNode>>,
+) {
+    let sources = collect_gravity_sources(attractor.iter());
+
+    query.iter_mut().for_each(|(i_entity, mut i_autopilot, i_elements, i_trans, i_velocity, i_fuel, i_thruster, i_mass)| {
+        let command = i_autopilot.command;
+        let Some(reference) = i_elements
+            .reference
+            .and_then(|reference| sources.iter().find(|source| source.entity == reference))
+        else {
+            fail(&mut commands, i_entity, command, AutopilotError::NoOrbit);
+            return;
+        };
+        let mu = gravitational_parameter(&reference.mass);
+        let relative_position = i_trans.translation.xy() - reference.position;
+        let relative_velocity = i_velocity.0 - reference.velocity.unwrap_or_default();
+
+        if i_autopilot.steps.is_none() {
+            // the target has to be outside of the orbited body and inside of its sphere of influence
+            if let Some(radius) = command.target_radius() {
+                let surface = hitboxes.get(reference.entity).map_or(0.0, |hitbox| hitbox.shape.bounding_radius());
+                if radius <= surface || reference.sphere_of_influence.is_some_and(|soi| radius >= soi) {
+                    fail(&mut commands, i_entity, command, AutopilotError::InvalidRadius { radius });
+                    return;
+                }
+            }
+            info!("Autopilot engaged: {}", command.to_text());
+        }
+        let steps = i_autopilot.steps.get_or_insert_with(|| command.steps(i_elements));
+
+        let available = i_fuel.amount * i_thruster.strength / i_mass.0;
+        let burn = estimate_delta_v(steps, mu, i_elements, relative_position, relative_velocity).and_then(|needed| {
+            if needed > available {
+                return Err(AutopilotError::InsufficientFuel { needed, available });
+            }
+            steps
+                .front()
+                .map(|step| plan_step(*step, mu, i_elements, relative_position, relative_velocity))
+                .transpose()
+        });
+
+        match burn {
+            Ok(Some(burn)) => {
+                steps.pop_front();
+                if burn.delta_v.length() >= MIN_DELTA_V {
+                    commands
+                        .entity(i_entity)
+                        .insert(ManeuverNode::new(clock.elapsed + burn.in_seconds, burn.delta_v));
+                }
+            }
+            Ok(None) => {
+                info!("Autopilot done: {}", command.to_text());
+                commands.entity(i_entity).remove::<Autopilot>();
+            }
+            Err(error) => fail(&mut commands, i_entity, command, error),
+        }
+    });
+}
+
+/// Give up if a burn of the autopilot ran out of fuel
+fn continue_after_maneuver(
+    event: On<ManeuverFinished>,
+    mut commands: Commands,
+    query: Query<(&Autopilot, &Fuel, &Thruster, &Mass)>,
+) {
+    let Ok((autopilot, fuel, thruster, mass)) = query.get(event.collector) else {
+        return;
+    };
+    if event.remaining >= MIN_DELTA_V {
+        let error = AutopilotError::InsufficientFuel {
+            needed: event.remaining,
+            available: fuel.amount * thruster.strength / mass.0,
+        };
+        fail(&mut commands, event.collector, autopilot.command, error);
+    }
+}
+
+fn fail(commands: &mut Commands, collector: Entity, command: AutopilotCommand, error: AutopilotError) {
+    warn!("Autopilot failed to {}: {error}", command.to_text());
+    commands.entity(collector).remove::<(Autopilot, ManeuverNode)>();
+    commands.trigger(AutopilotFailed {
+        collector,
+        command,
+        error,
+    });
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum AutopilotKey {
+    Circularize,
+    CircularizeAtApoapsis,
+    SetPeriapsis,
+    HohmannTransfer,
+    Off,
+}
+
+fn record_autopilot_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    collector: Query<&OrbitalElements, With<NavigationInstruments>>,
+    bodies: Query<&Transform>,
+    mut pending_inputs: ResMut<PendingInputs>,
+) {
+    let Some((_, key)) = AUTOPILOT_KEYS.iter().find(|(key, _)| keyboard_input.just_pressed(*key)) else {
+        return;
+    };
+    let Ok(elements) = collector.single() else {
+        return;
+    };
+
+    // radius targets are picked with the mouse, as the distance from the orbited body
+    let (camera, camera_transform) = *camera;
+    let cursor_radius = || {
+        let cursor = window
+            .cursor_position()
+            .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())?;
+        let reference = bodies.get(elements.reference?).ok()?;
+        Some(cursor.distance(reference.translation.xy()))
+    };
+
+    let command = match key {
+        AutopilotKey::Circularize => Some(AutopilotCommand::Circularize),
+        AutopilotKey::CircularizeAtApoapsis => Some(AutopilotCommand::CircularizeAtApoapsis),
+        AutopilotKey::SetPeriapsis => match cursor_radius() {
+            Some(radius) => Some(AutopilotCommand::SetPeriapsis { radius }),
+            None => return,
+        },
+        AutopilotKey::HohmannTransfer => match cursor_radius() {
+            Some(radius) => Some(AutopilotCommand::HohmannTransfer { radius }),
+            None => return,
+        },
+        AutopilotKey::Off => None,
+    };
+    pending_inputs.push(PlayerInput::Autopilot { command });
+}
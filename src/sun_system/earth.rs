@@ -2,13 +2,33 @@ use crate::asset_tracking::LoadResource;
 use crate::screens::Screen;
 use bevy::prelude::*;
 use crate::GameplaySystem;
-use crate::launching::{make_launchpad, LaunchPad};
-use crate::sun_system::Sun;
+use crate::launching::make_launchpad;
+use crate::collision::HitBox;
+use crate::collision::layers::CollisionLayer;
+use crate::collision::shape::{HitBoxFromSprite, HitBoxShape};
+use crate::physics::calc_gravity::{calc_sphere_of_influence, Attractee, Attractor, SphereOfInfluence};
+use crate::physics::directional_forces::Mass;
+use crate::physics::orbital_elements::{circular_speed, gravitational_parameter};
+use crate::physics::velocity::Velocity;
+use crate::sun_system::SUN_MASS;
+
+/// Distance of the earth to the sun at the start of the game
+const EARTH_ORBIT_RADIUS: f32 = 100.0;
+
+/// Heavy enough that collectors can be parked in an orbit around the earth
+pub const EARTH_MASS: f32 = 2_000_000_000_000.0;
+
+const EARTH_SCALE: f32 = 0.004;
+/// The earth sprite is a disc filling an image 2000 pixels across
+const EARTH_SPRITE_RADIUS: f32 = 1000.0;
 
 pub(super) fn plugin(app: &mut App) {
-    app.load_resource::<EarthAssets>();
     app.add_systems(OnEnter(Screen::Gameplay), init_earth);
-    app.add_systems(Update, move_earth_around_sun.in_set(GameplaySystem));
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.load_resource::<EarthAssets>();
+    app.add_observer(add_earth_sprite);
     app.add_systems(Update, draw_arrow.in_set(GameplaySystem));
 }
 
@@ -33,41 +53,40 @@ impl FromWorld for EarthAssets {
 #[require(Transform)]
 pub struct Earth;
 
-fn init_earth(mut commands: Commands, assets: Res<EarthAssets>) {
+fn init_earth(mut commands: Commands) {
     info!("Init earth");
 
+    // start on a circular orbit around the sun, from there on the physics simulation moves the earth
+    let orbital_speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), EARTH_ORBIT_RADIUS);
+
     commands.spawn((
         Name::new("Earth"),
         Earth,
-        Transform::from_translation(Vec3::new(100.0, 0.0, 0.0)).with_scale(Vec3::splat(0.004)),
-        Sprite::from(assets.earth.clone()),
+        // the earth is pulled by the sun and pulls on everything close to it
+        Attractee,
+        Attractor,
+        HitBox {
+            shape: HitBoxShape::Circle { radius: EARTH_SPRITE_RADIUS * EARTH_SCALE },
+            layer: CollisionLayer::CelestialBody,
+            ..default()
+        },
+        HitBoxFromSprite::Circle,
+        Mass(EARTH_MASS),
+        Velocity(Vec2::new(0.0, orbital_speed)),
+        SphereOfInfluence {
+            radius: calc_sphere_of_influence(EARTH_ORBIT_RADIUS, &Mass(EARTH_MASS), &Mass(SUN_MASS)),
+        },
+        Transform::from_translation(Vec3::new(EARTH_ORBIT_RADIUS, 0.0, 0.0)).with_scale(Vec3::splat(EARTH_SCALE)),
         children![ 
             make_launchpad(),
         ]
     ));
 }
 
-fn move_earth_around_sun(
-    mut earth_query: Query<&mut Transform, With<Earth>>,
-    sun_query: Query<&Transform, (With<Sun>, Without<Earth>)>,
-    mut launch_pad_query: Query<&mut Transform, (With<LaunchPad>, Without<Earth>, Without<Sun>)>,
-    time: Res<Time>
-) {
-    let sun_transform = sun_query.single();
-    let sun_position = sun_transform.unwrap().translation;
-
-    for mut earth_transform in earth_query.iter_mut() {
-        let angle_speed = 0.1;
-        let radius = earth_transform.translation.distance(sun_position);
-        let angle = time.elapsed_secs() * angle_speed;
-
-        let new_x = sun_position.x + radius * angle.cos();
-        let new_y = sun_position.y + radius * angle.sin();
-        earth_transform.translation = Vec3::new(new_x, new_y, earth_transform.translation.z);
-        // FUCK, I DONT KNOW WHY BUT TRANSFORM PROPAGATION IS BROKEN :(
-        let mut launch_pad_transform = launch_pad_query.single_mut().unwrap();
-        launch_pad_transform.translation = earth_transform.translation;
-    }
+fn add_earth_sprite(event: On<Add, Earth>, mut commands: Commands, assets: Res<EarthAssets>) {
+    commands
+        .entity(event.entity)
+        .insert(Sprite::from(assets.earth.clone()));
 }
 
 fn draw_arrow(
@@ -0,0 +1,341 @@
+//! Maneuver nodes are burns which are planned on the projected orbit of the selected collector
+//! and then executed by its thruster once the collector gets there.
+//!
+//! Right click on the projected orbit to place a node, drag its handles with the right mouse button
+//! to change the prograde and radial delta-v and press backspace to remove it again.
+
+use crate::launching::Fuel;
+use crate::physics::calc_gravity::{Attractor, GravitySource, GravitySourceData, collect_gravity_sources, dominant_body};
+use crate::physics::directional_forces::{Mass, ThrustForce};
+use crate::physics::integrator::integrate_motion;
+use crate::physics::velocity::Velocity;
+use crate::replay::{ApplyPlayerInput, PendingInputs, PlayerInput, SimulationClock, accepts_player_input};
+use crate::sun_system::navigation_instruments::{NavigationInstruments, OrbitProjection, ProjectedNode, draw_nav_projections};
+use crate::sun_system::thruster::{Thruster, apply_thrust_force};
+use crate::{AppSystems, GameplaySystem};
+use bevy::color::palettes::css::{AQUA, ORANGE, YELLOW};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+const MANEUVER_BUTTON: MouseButton = MouseButton::Right;
+const CANCEL_MANEUVER_KEY: KeyCode = KeyCode::Backspace;
+
+/// Distance of the handles from the node in pixels
+const HANDLE_DISTANCE: f32 = 40.0;
+/// Size of the handles in pixels
+const HANDLE_RADIUS: f32 = 8.0;
+/// How close to the projected orbit a click has to be to place a node, in pixels
+const PICK_DISTANCE: f32 = 12.0;
+/// How much delta-v is added for every pixel a handle is dragged
+const DELTA_V_PER_PIXEL: f32 = 0.02;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_observer(apply_maneuver_input);
+    app.add_systems(
+        FixedUpdate,
+        execute_maneuver_nodes
+            .in_set(GameplaySystem)
+            .in_set(AppSystems::Physics)
+            .after(apply_thrust_force)
+            .before(integrate_motion),
+    );
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.init_resource::<ManeuverEditor>();
+    app.add_systems(
+        Update,
+        edit_maneuver_node
+            .run_if(accepts_player_input)
+            .in_set(GameplaySystem)
+            .in_set(AppSystems::RecordInput),
+    );
+    app.add_systems(
+        Update,
+        draw_maneuver_node
+            .after(draw_nav_projections)
+            .in_set(GameplaySystem)
+            .in_set(AppSystems::Update),
+    );
+}
+
+/// A planned burn of a collector
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct ManeuverNode {
+    /// Simulation time (see [`SimulationClock`]) on which the burn is centered
+    pub at: f32,
+    /// The planned change of velocity, `x` along prograde and `y` along radial-out
+    pub delta_v: Vec2,
+    /// How much of the delta-v has already been burned
+    pub burned: f32,
+    /// Direction of the burn, fixed once it starts so that it doesn't turn with the orbit
+    pub direction: Option<Vec2>,
+}
+
+impl ManeuverNode {
+    pub fn new(at: f32, delta_v: Vec2) -> Self {
+        Self {
+            at,
+            delta_v,
+            burned: 0.0,
+            direction: None,
+        }
+    }
+
+    /// How much delta-v still has to be burned
+    pub fn remaining(&self) -> f32 {
+        (self.delta_v.length() - self.burned).max(0.0)
+    }
+
+    /// The change of velocity that is still to come, given the prograde and radial-out directions at the node
+    pub fn remaining_delta_v(&self, prograde: Vec2, radial_out: Vec2) -> Vec2 {
+        let direction = self
+            .direction
+            .unwrap_or_else(|| (prograde * self.delta_v.x + radial_out * self.delta_v.y).normalize_or_zero());
+        direction * self.remaining()
+    }
+}
+
+/// Triggered when a collector is done with its maneuver node, either because it burned all of it or ran out of fuel
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct ManeuverFinished {
+    pub collector: Entity,
+    /// Delta-v that could not be burned
+    pub remaining: f32,
+}
+
+/// Prograde and radial-out directions of a body relative to the body it orbits at `position`
+pub fn maneuver_frame(sources: &[GravitySource], entity: Entity, velocity: Vec2, position: Vec2) -> Option<(Vec2, Vec2)> {
+    let reference = dominant_body(sources, Some(entity), position)?;
+    orbit_frame(
+        position - reference.position,
+        velocity - reference.velocity.unwrap_or_default(),
+    )
+}
+
+/// Prograde and radial-out directions given the position and velocity relative to the orbited body
+pub fn orbit_frame(relative_position: Vec2, relative_velocity: Vec2) -> Option<(Vec2, Vec2)> {
+    let prograde = relative_velocity.try_normalize()?;
+    let radial_out = (relative_position - prograde * relative_position.dot(prograde))
+        .try_normalize()
+        .unwrap_or(prograde.perp());
+    Some((prograde, radial_out))
+}
+
+fn apply_maneuver_input(
+    input: On<ApplyPlayerInput>,
+    mut commands: Commands,
+    query: Query<(Entity, Option<&ManeuverNode>), With<NavigationInstruments>>,
+) {
+    match input.0 {
+        PlayerInput::PlanManeuver { at, prograde, radial } => {
+            for (entity, node) in query.iter() {
+                if node.is_some_and(|node| node.direction.is_some()) {
+                    // the burn already started
+                    continue;
+                }
+                commands.entity(entity).insert(ManeuverNode::new(at, Vec2::new(prograde, radial)));
+            }
+        }
+        PlayerInput::CancelManeuver => {
+            for (entity, _) in query.iter() {
+                commands.entity(entity).remove::<ManeuverNode>();
+            }
+        }
+        _ => {}
+    }
+}
+
+type ManeuveringCollectorData = (
+    Entity,
+    &'static mut ManeuverNode,
+    &'static Thruster,
+    &'static mut Fuel,
+    &'static Mass,
+    &'static Velocity,
+    &'static Transform,
+    &'static mut ThrustForce,
+);
+
+/// Fire the thruster of collectors that reached their maneuver node until the planned delta-v is burned
+pub(super) fn execute_maneuver_nodes(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    time: Res<Time>,
+    attractor: Query<GravitySourceData, With<Attractor>>,
+    mut query: Query<ManeuveringCollectorData>,
+) {
+    let sources = collect_gravity_sources(attractor.iter());
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    query.iter_mut().for_each(|(i_entity, mut i_node, i_thruster, mut i_fuel, i_mass, i_velocity, i_trans, mut i_thrust)| {
+        let acceleration = i_thruster.strength / i_mass.0;
+
+        if i_node.direction.is_none() {
+            // start early so that half of the burn happens before and half after the node
+            let burn_time = i_node.remaining() / acceleration;
+            if clock.elapsed < i_node.at - burn_time / 2.0 {
+                return;
+            }
+            let Some((prograde, radial_out)) = maneuver_frame(&sources, i_entity, i_velocity.0, i_trans.translation.xy()) else {
+                return;
+            };
+            i_node.direction = Some(i_node.remaining_delta_v(prograde, radial_out).normalize_or_zero());
+            info!("Starting maneuver burn of {:.2}", i_node.remaining());
+        }
+
+        // fuel is used up at the same rate as when firing the thruster by hand
+        let burn = i_node.remaining().min(acceleration * dt).min(i_fuel.amount * acceleration);
+        i_fuel.amount = (i_fuel.amount - burn / acceleration).max(0.0);
+        i_thrust.0 += i_node.direction.unwrap_or_default() * (burn / dt) * i_mass.0;
+        i_node.burned += burn;
+
+        if i_node.remaining() <= f32::EPSILON || i_fuel.amount <= 0.0 {
+            info!("Finished maneuver burn, {:.2} delta-v left", i_node.remaining());
+            commands.entity(i_entity).remove::<ManeuverNode>();
+            commands.trigger(ManeuverFinished {
+                collector: i_entity,
+                remaining: i_node.remaining(),
+            });
+        }
+    });
+}
+
+/// The mouse cursor in world coordinates
+#[derive(SystemParam)]
+struct WorldCursor<'w, 's> {
+    window: Single<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera: Single<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+}
+
+impl WorldCursor<'_, '_> {
+    fn position(&self) -> Option<Vec2> {
+        let (camera, camera_transform) = *self.camera;
+        let cursor = self.window.cursor_position()?;
+        camera.viewport_to_world_2d(camera_transform, cursor).ok()
+    }
+
+    /// How many world units a pixel is
+    fn scale(&self) -> f32 {
+        self.camera.1.scale().x
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+struct ManeuverEditor {
+    dragging: Option<HandleDrag>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct HandleDrag {
+    /// Which component of the delta-v is changed by the handle
+    axis: Vec2,
+    direction: Vec2,
+    start_cursor: Vec2,
+    start_delta_v: Vec2,
+}
+
+/// The handles of a node, as delta-v axis, direction and position on screen and color
+fn handles(node: &ProjectedNode, scale: f32) -> [(Vec2, Vec2, Vec2, Srgba); 4] {
+    [
+        (Vec2::X, node.prograde, YELLOW),
+        (Vec2::NEG_X, -node.prograde, YELLOW),
+        (Vec2::Y, node.radial_out, AQUA),
+        (Vec2::NEG_Y, -node.radial_out, AQUA),
+    ]
+    .map(|(axis, direction, color)| (axis, direction, node.position + direction * HANDLE_DISTANCE * scale, color))
+}
+
+fn edit_maneuver_node(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    world_cursor: WorldCursor,
+    collector: Query<(&OrbitProjection, Option<&ManeuverNode>), With<NavigationInstruments>>,
+    clock: Res<SimulationClock>,
+    mut editor: ResMut<ManeuverEditor>,
+    mut pending_inputs: ResMut<PendingInputs>,
+) {
+    let Ok((projection, node)) = collector.single() else {
+        editor.dragging = None;
+        return;
+    };
+    if mouse_input.just_released(MANEUVER_BUTTON) {
+        editor.dragging = None;
+    }
+    if keyboard_input.just_pressed(CANCEL_MANEUVER_KEY) && node.is_some() {
+        editor.dragging = None;
+        pending_inputs.push(PlayerInput::CancelManeuver);
+        return;
+    }
+
+    let Some(cursor) = world_cursor.position() else {
+        return;
+    };
+    // pixels to world units
+    let scale = world_cursor.scale();
+
+    if mouse_input.just_pressed(MANEUVER_BUTTON) {
+        // grab a handle of the existing node
+        if let (Some(node), Some(projected)) = (node, projection.node)
+            && let Some((axis, direction, ..)) = handles(&projected, scale)
+                .into_iter()
+                .find(|(_, _, position, _)| position.distance(cursor) <= HANDLE_RADIUS * scale)
+        {
+            editor.dragging = Some(HandleDrag {
+                axis,
+                direction,
+                start_cursor: cursor,
+                start_delta_v: node.delta_v,
+            });
+            return;
+        }
+
+        // or move the node to where the orbit was clicked, keeping its delta-v
+        let closest = projection
+            .points
+            .iter()
+            .map(|(time, position)| (*time, position.distance(cursor)))
+            .filter(|(_, distance)| *distance <= PICK_DISTANCE * scale)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((time, _)) = closest {
+            let delta_v = node.map(|node| node.delta_v).unwrap_or_default();
+            pending_inputs.push(PlayerInput::PlanManeuver {
+                at: clock.elapsed + time,
+                prograde: delta_v.x,
+                radial: delta_v.y,
+            });
+        }
+    } else if mouse_input.pressed(MANEUVER_BUTTON)
+        && let (Some(drag), Some(node)) = (editor.dragging, node)
+    {
+        let pixels = (cursor - drag.start_cursor).dot(drag.direction) / scale;
+        let delta_v = drag.start_delta_v + drag.axis * pixels * DELTA_V_PER_PIXEL;
+        if delta_v != node.delta_v {
+            pending_inputs.push(PlayerInput::PlanManeuver {
+                at: node.at,
+                prograde: delta_v.x,
+                radial: delta_v.y,
+            });
+        }
+    }
+}
+
+fn draw_maneuver_node(
+    mut gizmos: Gizmos,
+    camera: Single<&GlobalTransform, With<Camera>>,
+    query: Query<&OrbitProjection, With<ManeuverNode>>,
+) {
+    let scale = camera.scale().x;
+
+    for projected in query.iter().filter_map(|projection| projection.node) {
+        gizmos.circle_2d(Isometry2d::from_translation(projected.position), HANDLE_RADIUS * scale, ORANGE);
+        for (_, _, position, color) in handles(&projected, scale) {
+            gizmos.line_2d(projected.position, position, color);
+            gizmos.circle_2d(Isometry2d::from_translation(position), HANDLE_RADIUS * scale * 0.5, color);
+        }
+    }
+}
@@ -1,48 +1,68 @@
 pub mod navigation_instruments;
 pub mod thruster;
-mod earth;
-pub(crate) mod asteroids;
+pub mod earth;
+pub mod asteroids;
+pub mod maneuver;
+pub mod autopilot;
 
 use crate::{AppSystems, GameplaySystem};
 use crate::asset_tracking::LoadResource;
 use crate::physics::calc_gravity::{Attractee, Attractor};
 use crate::physics::directional_forces::{GravityForce, Mass};
+use crate::physics::integrator::integrate_motion;
 use crate::physics::velocity::Velocity;
+use crate::replay::accepts_player_input;
 use crate::screens::Screen;
-use crate::sun_system::navigation_instruments::NavigationInstruments;
+use crate::sun_system::navigation_instruments::OrbitProjection;
 use crate::sun_system::thruster::{thruster_use_fuel, Thruster, ThrusterDirection};
-use bevy::input::common_conditions::{input_just_pressed, input_just_released};
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use crate::collision::HitBox;
+use crate::collision::layers::CollisionLayer;
+use crate::collision::shape::{HitBoxFromSprite, HitBoxShape};
+use crate::collision::damage::Health;
 
 
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((earth::plugin, asteroids::plugin));
-    app.load_resource::<SolarSystemAssets>();
+    app.add_plugins((earth::plugin, asteroids::plugin, maneuver::plugin, autopilot::plugin));
+    app.add_systems(OnEnter(Screen::Gameplay), init_sun_system);
     app.add_systems(
         FixedUpdate,
-        (thruster::apply_thrust_force)
+        (thruster::update_thruster_strength, thruster::apply_thrust_force)
+            .chain()
             .in_set(AppSystems::Physics)
+            .before(integrate_motion)
             .run_if(in_state(Screen::Gameplay)),
     );
+    app.add_observer(thruster::apply_thruster_input);
+
+    app.add_systems(FixedUpdate, thruster_use_fuel.in_set(GameplaySystem));
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_plugins((
+        earth::presentation_plugin,
+        asteroids::presentation_plugin,
+        maneuver::presentation_plugin,
+        autopilot::presentation_plugin,
+        navigation_instruments::presentation_plugin,
+    ));
+    app.load_resource::<SolarSystemAssets>();
+    app.add_observer(add_sun_sprite);
+    app.init_resource::<thruster::ThrottleControl>();
     app.add_systems(
         Update,
-        thruster::toggle_thruster
-            .run_if(
-                input_just_pressed(thruster::THRUSTER_KEY)
-                    .or(input_just_released(thruster::THRUSTER_KEY)),
-            )
+        thruster::record_thruster_input
+            .run_if(accepts_player_input)
+            .in_set(GameplaySystem)
             .in_set(AppSystems::RecordInput),
     );
     app.add_systems(
         Update,
-        navigation_instruments::draw_nav_projections
-            .run_if(in_state(Screen::Gameplay))
+        thruster::draw_thrust_direction
+            .in_set(GameplaySystem)
             .in_set(AppSystems::Update),
     );
-
-    app.add_systems(Update, thruster_use_fuel.in_set(GameplaySystem));
 }
 
 #[derive(Resource, Asset, Clone, Reflect)]
@@ -84,6 +104,7 @@ pub struct SolarSystemAssets {
 }
 
 #[derive(Component)]
+#[require(OrbitProjection, Health)]
 pub struct Satellite;
 
 #[derive(Component, Debug, Copy, Clone)]
@@ -95,6 +116,13 @@ pub struct Level{
 #[derive(Component)]
 pub struct Sun;
 
+/// Mass of the sun, the earth is put on a circular orbit based on it
+pub const SUN_MASS: f32 = 100_000_000_000_000.0;
+
+const SUN_SCALE: f32 = 0.02;
+/// The sun sprite is a disc filling an image 2000 pixels across
+const SUN_SPRITE_RADIUS: f32 = 1000.0;
+
 
 impl FromWorld for SolarSystemAssets {
     fn from_world(world: &mut World) -> Self {
@@ -115,18 +143,25 @@ impl FromWorld for SolarSystemAssets {
     }
 }
 
-pub fn init_sun_system(mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>) {
+pub fn init_sun_system(mut commands: Commands) {
     info!("Adding sun");
     commands.spawn((
         Attractor,
-        Level { level: 0. }, // needed for easy collisions
         HitBox {
-            radius: 20.0
+            shape: HitBoxShape::Circle { radius: SUN_SPRITE_RADIUS * SUN_SCALE },
+            layer: CollisionLayer::CelestialBody,
+            ..default()
         },
-        Mass(100_000_000_000_000.0),
+        HitBoxFromSprite::Circle,
+        Mass(SUN_MASS),
         Name::new("Sun"),
-        Transform::from_translation(Vec3::ZERO).with_scale(Vec3::splat(0.02)),
-        Sprite::from(solar_system_assets.sun.clone()),
+        Transform::from_translation(Vec3::ZERO).with_scale(Vec3::splat(SUN_SCALE)),
         Sun
     ));
+}
+
+fn add_sun_sprite(event: On<Add, Sun>, mut commands: Commands, solar_system_assets: Res<SolarSystemAssets>) {
+    commands
+        .entity(event.entity)
+        .insert(Sprite::from(solar_system_assets.sun.clone()));
 }
\ No newline at end of file
@@ -1,86 +1,466 @@
 use crate::collision::{HitBox, is_colliding};
-use crate::physics::calc_gravity::{Attractee, Attractor, calc_gravity_force};
-use crate::physics::directional_forces::{Mass, calc_velocity_change};
-use crate::physics::velocity::{Velocity, calc_position_change};
-use bevy::color::palettes::basic::GRAY;
+use crate::physics::calc_gravity::{
+    Attractee, Attractor, GravityModel, GravitySource, GravitySourceData, collect_gravity_sources, dominant_body,
+};
+use crate::physics::directional_forces::Mass;
+use crate::physics::integrator::Integrator;
+use crate::physics::velocity::Velocity;
+use crate::replay::SimulationClock;
+use crate::screens::Screen;
+use crate::sun_system::maneuver::{ManeuverNode, maneuver_frame};
+use crate::sun_system::thruster::Thruster;
+use crate::AppSystems;
+use bevy::color::palettes::basic::{GRAY, WHITE};
+use bevy::color::palettes::css::ORANGE;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::ecs::system::SystemParam;
+use bevy::input::common_conditions::input_just_pressed;
+use bevy::platform::time::Instant;
 use bevy::prelude::*;
 use std::f32::consts::PI;
 
+/// Seconds per projection step, 32 physics ticks at bevys default fixed timestep of 1/64 s.
+///
+/// Projections use the same integrator as the simulation but with much longer steps, so that they reach around a
+/// whole orbit. The projected path therefore slowly drifts away from the one the body actually flies, most of all
+/// close to the sun, which is why projections are recomputed once the body strays from them.
 const PROJECTION_DELTA: f32 = 0.5;
 const PROJECTION_MAX_COUNT: usize = 250;
 
+/// Toggles drawing the projections of all collectors instead of only the selected one
+const SHOW_ALL_KEY: KeyCode = KeyCode::KeyO;
+/// Projections are recomputed after this many seconds, even if the body flies exactly as projected
+const PROJECTION_MAX_AGE: f32 = 2.0;
+/// How far a body may stray from its projection before it is recomputed
+const PROJECTION_TOLERANCE: f32 = 0.5;
+
+/// Milliseconds spent recomputing projections per frame
+pub const PROJECTION_COST: DiagnosticPath = DiagnosticPath::const_new("projections/cost");
+/// Number of projections recomputed per frame
+pub const PROJECTION_UPDATES: DiagnosticPath = DiagnosticPath::const_new("projections/updates");
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.init_resource::<ProjectionSettings>();
+    app.register_diagnostic(Diagnostic::new(PROJECTION_COST).with_suffix("ms"));
+    app.register_diagnostic(Diagnostic::new(PROJECTION_UPDATES));
+    app.add_systems(
+        Update,
+        (
+            toggle_all_projections.run_if(input_just_pressed(SHOW_ALL_KEY)),
+            expire_temporary_projections,
+            update_nav_projections,
+            draw_nav_projections,
+        )
+            .chain()
+            .run_if(in_state(Screen::Gameplay))
+            .in_set(AppSystems::Update),
+    );
+}
+
 #[derive(Component, Debug, Default, Copy, Clone)]
-#[require(Transform, Velocity, Mass, HitBox)]
+#[require(Transform, Velocity, Mass, HitBox, OrbitProjection)]
 pub struct NavigationInstruments;
 
-pub fn draw_nav_projections(
-    mut gizmos: Gizmos,
-    attractor: Query<(&Transform, &Mass, &HitBox), With<Attractor>>,
-    query: Query<(&Transform, &Mass, &Velocity, &HitBox), (With<NavigationInstruments>, With<Attractee>)>,
+#[derive(Resource, Debug, Copy, Clone, PartialEq)]
+pub struct ProjectionSettings {
+    /// Whether the projections of all collectors are drawn or only the one of the selected collector
+    pub show_all: bool,
+    /// How many projections may be recomputed in a single frame, the selected collector goes first
+    pub max_updates_per_frame: usize,
+}
+
+impl Default for ProjectionSettings {
+    fn default() -> Self {
+        Self {
+            show_all: false,
+            max_updates_per_frame: 4,
+        }
+    }
+}
+
+/// Shows the projection of a body that isn't selected for a while, e.g. the path of an incoming asteroid swarm
+#[derive(Component, Debug, Clone)]
+pub struct TemporaryProjection {
+    pub timer: Timer,
+    pub color: Srgba,
+}
+
+impl TemporaryProjection {
+    pub fn new(seconds: f32, color: Srgba) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+            color,
+        }
+    }
+}
+
+/// The projected path of a body, computed once and then reused until the body strays from it
+#[derive(Component, Debug, Default, Clone)]
+pub struct OrbitProjection {
+    /// Seconds from now until the body gets to a point and where that point is drawn, up to the maneuver node
+    pub points: Vec<(f32, Vec2)>,
+    /// Where the planned maneuver node is drawn together with its prograde and radial-out directions
+    pub node: Option<ProjectedNode>,
+    /// Simulation time at which the path was computed, `None` if it needs to be computed
+    computed_at: Option<f32>,
+    path: Vec<ProjectedPoint>,
+    maneuver: Option<ProjectedManeuver>,
+    /// The maneuver node the path was computed for
+    planned: Option<ManeuverNode>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ProjectedNode {
+    pub position: Vec2,
+    pub prograde: Vec2,
+    pub radial_out: Vec2,
+}
+
+/// A point of a projected path, stored relative to the body orbited there
+/// so that it can be drawn around where that body is right now
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ProjectedPoint {
+    /// Simulation time at which the body gets here
+    at: f32,
+    reference: Entity,
+    offset: Vec2,
+    /// Whether the planned maneuver is already done at this point
+    after_maneuver: bool,
+    /// Whether a sphere of influence is entered or left here
+    reference_changed: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ProjectedManeuver {
+    reference: Entity,
+    offset: Vec2,
+    prograde: Vec2,
+    radial_out: Vec2,
+}
+
+impl OrbitProjection {
+    /// Simulation time at which the path was last computed
+    pub fn computed_at(&self) -> Option<f32> {
+        self.computed_at
+    }
+
+    /// Number of points on the projected path, including where the body was when it was computed
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Whether no path has been projected, e.g. because the body isn't orbiting anything
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Where the body should be at `time` according to the projection, relative to the orbited body
+    fn expected_at(&self, time: f32) -> Option<(Entity, Vec2)> {
+        let next = self.path.iter().position(|point| point.at >= time)?;
+        let to = self.path[next];
+        let Some(from) = next.checked_sub(1).map(|previous| self.path[previous]) else {
+            return Some((to.reference, to.offset));
+        };
+        if from.reference != to.reference {
+            return Some((to.reference, to.offset));
+        }
+        let t = ((time - from.at) / (to.at - from.at)).clamp(0.0, 1.0);
+        Some((to.reference, from.offset.lerp(to.offset, t)))
+    }
+
+    /// Whether the projection is missing, outdated or doesn't match how the body flies anymore
+    fn needs_update(&self, now: f32, position: Vec2, thrusting: bool, node: Option<&ManeuverNode>, sources: &[GravitySource]) -> bool {
+        let Some(computed_at) = self.computed_at else {
+            return true;
+        };
+        if thrusting || node != self.planned.as_ref() || now - computed_at >= PROJECTION_MAX_AGE {
+            return true;
+        }
+        // the start of the path is exactly where the body was when it was computed
+        let Some((reference, offset)) = self.expected_at(now) else {
+            return true;
+        };
+        position.distance(reference_position(sources, reference) + offset) > PROJECTION_TOLERANCE
+    }
+}
+
+fn toggle_all_projections(mut settings: ResMut<ProjectionSettings>) {
+    settings.show_all = !settings.show_all;
+}
+
+fn expire_temporary_projections(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut TemporaryProjection)>,
 ) {
-    let (attractor_trans, attractor_mass, attractor_hitbox) = attractor
-        .single()
-        .expect("Cannot draw orbital projections if there is no attractor in the world");
-
-    query.iter().for_each(|(i_trans, i_mass, i_velocity, i_hitbox)| {
-        draw_orbit_projection(
-            &mut gizmos,
-            attractor_trans,
-            attractor_mass,
-            attractor_hitbox,
-            i_trans,
-            i_mass,
-            i_velocity,
-            i_hitbox,
-        )
+    for (entity, mut temporary) in &mut query {
+        if temporary.timer.tick(time.delta()).is_finished() {
+            commands.entity(entity).remove::<(TemporaryProjection, OrbitProjection)>();
+        }
+    }
+}
+
+type ProjectedBodyData = (
+    Entity,
+    &'static Transform,
+    &'static Mass,
+    &'static Velocity,
+    Option<&'static HitBox>,
+    Option<&'static Thruster>,
+    Option<&'static ManeuverNode>,
+    &'static mut OrbitProjection,
+    Has<NavigationInstruments>,
+    Has<TemporaryProjection>,
+);
+
+/// How bodies are moved along their projections and what they can crash into
+#[derive(SystemParam)]
+pub struct ProjectionPhysics<'w, 's> {
+    integrator: Res<'w, Integrator>,
+    gravity: Res<'w, GravityModel>,
+    attractor: Query<'w, 's, GravitySourceData, With<Attractor>>,
+    obstacles: Query<'w, 's, &'static HitBox, With<Attractor>>,
+}
+
+/// A body as it is when its orbit is projected
+struct ProjectedBody<'a> {
+    entity: Entity,
+    transform: &'a Transform,
+    mass: &'a Mass,
+    velocity: &'a Velocity,
+    /// Without a hitbox, e.g. for asteroid swarms, the projection goes right through attractors
+    hitbox: Option<&'a HitBox>,
+    node: Option<&'a ManeuverNode>,
+}
+
+/// Recompute the projections that don't match their body anymore, within the budget per frame
+pub fn update_nav_projections(
+    physics: ProjectionPhysics,
+    clock: Res<SimulationClock>,
+    settings: Res<ProjectionSettings>,
+    mut diagnostics: Diagnostics,
+    mut query: Query<ProjectedBodyData, With<Attractee>>,
+) {
+    let start = Instant::now();
+    let sources = collect_gravity_sources(physics.attractor.iter());
+
+    let mut outdated = query
+        .iter()
+        .filter(|(.., selected, temporary)| *selected || *temporary || settings.show_all)
+        .filter(|(_, transform, _, _, _, thruster, node, projection, ..)| {
+            let thrusting = thruster.is_some_and(|thruster| thruster.active);
+            projection.needs_update(clock.elapsed, transform.translation.xy(), thrusting, *node, &sources)
+        })
+        .map(|(entity, .., projection, selected, _)| (entity, selected, projection.computed_at))
+        .collect::<Vec<_>>();
+    // the selected collector first, then the ones that waited the longest
+    outdated.sort_by(|(_, selected_a, at_a), (_, selected_b, at_b)| {
+        selected_b
+            .cmp(selected_a)
+            .then(at_a.unwrap_or(f32::NEG_INFINITY).total_cmp(&at_b.unwrap_or(f32::NEG_INFINITY)))
     });
+    outdated.truncate(settings.max_updates_per_frame);
+
+    for (entity, ..) in &outdated {
+        let Ok((_, transform, mass, velocity, hitbox, _, node, mut projection, ..)) = query.get_mut(*entity) else {
+            continue;
+        };
+        let body = ProjectedBody {
+            entity: *entity,
+            transform,
+            mass,
+            velocity,
+            hitbox,
+            node,
+        };
+        project_orbit(&physics, &sources, body, clock.elapsed, &mut projection);
+    }
+
+    diagnostics.add_measurement(&PROJECTION_COST, || start.elapsed().as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&PROJECTION_UPDATES, || outdated.len() as f64);
 }
 
-fn draw_orbit_projection(
-    gizmos: &mut Gizmos,
-    attractor_trans: &Transform,
-    attractor_mass: &Mass,
-    attractor_hitbox: &HitBox,
-    transform: &Transform,
-    mass: &Mass,
-    velocity: &Velocity,
-    hitbox: &HitBox,
+/// Project the orbit of a body by simulating it together with all attractors.
+///
+/// Like in patched conics the projection is stored relative to the body that is orbited at each point,
+/// so that e.g. an orbit around the moving earth is drawn around where the earth is now.
+/// Whenever a sphere of influence is entered or left the reference body changes and this is marked on the projection.
+/// A planned maneuver is applied as an instant change of velocity.
+fn project_orbit(
+    physics: &ProjectionPhysics,
+    sources: &[GravitySource],
+    body: ProjectedBody,
+    now: f32,
+    projection: &mut OrbitProjection,
 ) {
+    let ProjectionPhysics {
+        integrator,
+        gravity,
+        obstacles,
+        ..
+    } = physics;
+    let ProjectedBody {
+        entity,
+        transform,
+        mass,
+        velocity,
+        hitbox,
+        node,
+    } = body;
     let mut degrees_covered = 0.0;
+    let mut pending_node = node;
+    projection.computed_at = Some(now);
+    projection.planned = node.copied();
+    projection.path.clear();
+    projection.maneuver = None;
 
-    let mut projected_trans = *transform;
-    let mut projected_velocity = *velocity;
+    let mut projected_pos = transform.translation.xy();
+    let mut projected_velocity = velocity.0;
+    let mut projected_sources = sources.to_vec();
 
-    for _ in 0..PROJECTION_MAX_COUNT {
-        let last_trans = projected_trans.translation.xy();
+    let Some(mut reference) = dominant_body(sources, Some(entity), projected_pos).map(|body| body.entity) else {
+        return;
+    };
+    projection.path.push(ProjectedPoint {
+        at: now,
+        reference,
+        offset: projected_pos - reference_position(sources, reference),
+        after_maneuver: false,
+        reference_changed: false,
+    });
 
-        let grav_force =
-            calc_gravity_force(attractor_mass, attractor_trans, mass, &projected_trans);
-        projected_velocity.0 += calc_velocity_change(grav_force, mass, PROJECTION_DELTA);
-        projected_trans.translation +=
-            calc_position_change(&projected_velocity, PROJECTION_DELTA).extend(0.0);
+    for i in 0..PROJECTION_MAX_COUNT {
+        let time = i as f32 * PROJECTION_DELTA;
 
-        // don't draw any more gizmos if they start colliding with the sun
-        if is_colliding(
-            attractor_trans,
-            attractor_hitbox,
-            &projected_trans,
-            hitbox,
-        ) {
+        if let Some(maneuver) = pending_node
+            && maneuver.at - now <= time
+        {
+            if let Some((prograde, radial_out)) = maneuver_frame(&projected_sources, entity, projected_velocity, projected_pos) {
+                projected_velocity += maneuver.remaining_delta_v(prograde, radial_out);
+                projection.maneuver = Some(ProjectedManeuver {
+                    reference,
+                    offset: projected_pos - reference_position(&projected_sources, reference),
+                    prograde,
+                    radial_out,
+                });
+            }
+            pending_node = None;
+            degrees_covered = 0.0;
+        }
+
+        let last_reference_pos = reference_position(&projected_sources, reference);
+        let last_pos = projected_pos;
+
+        (projected_pos, projected_velocity) =
+            integrator.step(projected_pos, projected_velocity, PROJECTION_DELTA, |position| {
+                gravity.force(&projected_sources, Some(entity), mass, position) / mass.0
+            });
+        integrator.step_sources(gravity, &mut projected_sources, PROJECTION_DELTA);
+
+        // stop the projection where it starts colliding with an attractor
+        if let Some(hitbox) = hitbox
+            && projected_sources
+                .iter()
+                .filter(|source| source.entity != entity)
+                .any(|source| {
+                    let Ok(obstacle_hitbox) = obstacles.get(source.entity) else {
+                        return false;
+                    };
+                    is_colliding(
+                        &Transform::from_translation(source.position.extend(0.0)),
+                        obstacle_hitbox,
+                        // the body is assumed to keep its current rotation along the way
+                        &transform.with_translation(projected_pos.extend(0.0)),
+                        hitbox,
+                    )
+                })
+        {
             break
         }
 
-        // draw the projection gizmo
-        gizmos.cross_2d(
-            Isometry2d::from_translation(projected_trans.translation.xy()),
-            1.0,
-            GRAY,
-        );
+        let Some(new_reference) = dominant_body(&projected_sources, Some(entity), projected_pos).map(|body| body.entity) else {
+            break;
+        };
+        projection.path.push(ProjectedPoint {
+            at: now + time + PROJECTION_DELTA,
+            reference: new_reference,
+            offset: projected_pos - reference_position(&projected_sources, new_reference),
+            after_maneuver: projection.maneuver.is_some(),
+            reference_changed: new_reference != reference,
+        });
+
+        if new_reference != reference {
+            // crossed the boundary of a sphere of influence, the orbit around the new reference starts here
+            reference = new_reference;
+            degrees_covered = 0.0;
+            continue;
+        }
 
-        // don't draw any more gizmos if we have covered 360°
-        degrees_covered += last_trans.angle_to(projected_trans.translation.xy()) * 180.0 / PI;
-        if degrees_covered >= 355.0 {
+        // stop once we have covered 360° around the reference body, unless there is still a maneuver coming up
+        let reference_pos = reference_position(&projected_sources, reference);
+        degrees_covered += (last_pos - last_reference_pos).angle_to(projected_pos - reference_pos) * 180.0 / PI;
+        if degrees_covered.abs() >= 355.0 && pending_node.is_none() {
             break;
         }
     }
 }
+
+type DrawnProjectionData = (
+    &'static mut OrbitProjection,
+    Has<NavigationInstruments>,
+    Option<&'static TemporaryProjection>,
+);
+
+/// Draw the cached projections around where the orbited bodies are right now
+pub fn draw_nav_projections(
+    mut gizmos: Gizmos,
+    clock: Res<SimulationClock>,
+    settings: Res<ProjectionSettings>,
+    attractor: Query<GravitySourceData, With<Attractor>>,
+    mut query: Query<DrawnProjectionData, With<Attractee>>,
+) {
+    let sources = collect_gravity_sources(attractor.iter());
+
+    query.iter_mut().for_each(|(mut i_projection, i_selected, i_temporary)| {
+        let projection = i_projection.as_mut();
+        projection.points.clear();
+        projection.node = None;
+        if !i_selected && i_temporary.is_none() && !settings.show_all {
+            return;
+        }
+
+        for point in projection.path.iter().skip(1).filter(|point| point.at >= clock.elapsed) {
+            let position = reference_position(&sources, point.reference) + point.offset;
+            if !point.after_maneuver {
+                projection.points.push((point.at - clock.elapsed, position));
+            }
+
+            if point.reference_changed {
+                gizmos.circle_2d(Isometry2d::from_translation(position), 3.0, WHITE);
+                continue;
+            }
+            let color = match (i_selected, point.after_maneuver) {
+                (_, true) => ORANGE,
+                (false, false) if let Some(temporary) = i_temporary => temporary.color,
+                (true, false) => GRAY,
+                // other collectors are drawn more subtle
+                (false, false) => GRAY.with_alpha(0.4),
+            };
+            gizmos.cross_2d(Isometry2d::from_translation(position), 1.0, color);
+        }
+
+        projection.node = projection.maneuver.map(|maneuver| ProjectedNode {
+            position: reference_position(&sources, maneuver.reference) + maneuver.offset,
+            prograde: maneuver.prograde,
+            radial_out: maneuver.radial_out,
+        });
+    });
+}
+
+fn reference_position(sources: &[GravitySource], reference: Entity) -> Vec2 {
+    sources
+        .iter()
+        .find(|source| source.entity == reference)
+        .map(|source| source.position)
+        .unwrap_or_default()
+}
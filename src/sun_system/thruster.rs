@@ -1,14 +1,42 @@
-use crate::physics::calc_gravity::Attractor;
+use crate::physics::calc_gravity::{collect_gravity_sources, dominant_body, Attractor, GravitySource, GravitySourceData};
 use crate::physics::directional_forces::ThrustForce;
 use crate::physics::velocity::Velocity;
+use bevy::color::palettes::css::ORANGE;
+use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
 use std::ops::Neg;
 use crate::launching::Fuel;
+use crate::replay::{ApplyPlayerInput, PendingInputs, PlayerInput};
+use crate::sun_system::Level;
+use crate::sun_system::navigation_instruments::NavigationInstruments;
 
-pub const THRUSTER_KEY: KeyCode = KeyCode::Space;
+/// Keys which fire the thruster of the selected collector in a direction for as long as they are held
+pub const THRUSTER_KEYS: [(KeyCode, ThrusterDirection); 5] = [
+    (KeyCode::Space, ThrusterDirection::Retrograde),
+    (KeyCode::KeyW, ThrusterDirection::Prograde),
+    (KeyCode::KeyS, ThrusterDirection::Retrograde),
+    (KeyCode::KeyA, ThrusterDirection::RadialIn),
+    (KeyCode::KeyD, ThrusterDirection::RadialOut),
+];
+
+/// While held the mouse wheel changes the throttle limit instead of zooming
+pub const THROTTLE_MODIFIER_KEYS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+
+/// Strength of the thruster by collector level, better collectors get stronger thrusters
+const THRUSTER_STRENGTH: [f32; 3] = [2.0, 3.0, 4.0];
+/// Radial burns are fired by the side thrusters, which push this much of the main engine and burn as much less fuel
+const SIDE_THRUSTER_POWER: f32 = 0.5;
+
+/// How long a thruster key has to be held to reach the throttle limit
+const THROTTLE_RAMP_SECONDS: f32 = 0.5;
+/// The throttle is sent to the simulation in steps of this size, so that recordings stay small
+const THROTTLE_STEP: f32 = 0.05;
+/// How much one step of the mouse wheel changes the throttle limit
+const THROTTLE_LIMIT_STEP: f32 = 0.1;
+/// Gamepad triggers pressed less than this are ignored
+const TRIGGER_DEADZONE: f32 = 0.05;
 
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
-#[allow(unused)]
 pub enum ThrusterDirection {
     /** Towards the velocity vector **/
     Prograde,
@@ -25,6 +53,8 @@ pub enum ThrusterDirection {
 pub struct Thruster {
     pub active: bool,
     pub strength: f32,
+    /// How much of the strength is used while firing, between 0 and 1
+    pub throttle: f32,
     pub direction: ThrusterDirection,
 }
 
@@ -34,78 +64,283 @@ impl Thruster {
             active: false,
             direction,
             strength,
+            throttle: 1.0,
         }
     }
+
+    /// A thruster as strong as a collector of the given level gets
+    pub fn for_level(level: &Level) -> Self {
+        Self::new(ThrusterDirection::Retrograde, thruster_strength(level))
+    }
 }
 
-pub fn toggle_thruster(mut query: Query<(&mut Thruster, Option<&Name>)>) {
+pub fn thruster_strength(level: &Level) -> f32 {
+    THRUSTER_STRENGTH[(level.level.round().max(1.0) as usize).min(THRUSTER_STRENGTH.len()) - 1]
+}
+
+impl ThrusterDirection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThrusterDirection::Prograde => "prograde",
+            ThrusterDirection::Retrograde => "retrograde",
+            ThrusterDirection::RadialIn => "radial-in",
+            ThrusterDirection::RadialOut => "radial-out",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ThrusterDirection::Prograde,
+            ThrusterDirection::Retrograde,
+            ThrusterDirection::RadialIn,
+            ThrusterDirection::RadialOut,
+        ]
+        .into_iter()
+        .find(|direction| direction.name() == name)
+    }
+
+    /// How much of its strength the thruster fires with in this direction. Fuel is used up at the same rate.
+    pub fn power(&self) -> f32 {
+        match self {
+            ThrusterDirection::Prograde | ThrusterDirection::Retrograde => 1.0,
+            ThrusterDirection::RadialIn | ThrusterDirection::RadialOut => SIDE_THRUSTER_POWER,
+        }
+    }
+}
+
+/// Where the throttle of the selected collector comes from: how long a key is held, the mouse wheel and gamepad triggers
+#[derive(Resource, Debug)]
+pub struct ThrottleControl {
+    /// Throttle reached when holding a thruster key, changed with shift and the mouse wheel
+    pub limit: f32,
+    /// How long the current thruster key has been held
+    held_for: f32,
+    /// What was last sent to the simulation
+    last: (Option<ThrusterDirection>, f32),
+}
+
+impl Default for ThrottleControl {
+    fn default() -> Self {
+        Self {
+            limit: 1.0,
+            held_for: 0.0,
+            last: (None, 0.0),
+        }
+    }
+}
+
+pub fn record_thruster_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut mouse_wheel: MessageReader<MouseWheel>,
+    time: Res<Time<Real>>,
+    mut control: ResMut<ThrottleControl>,
+    mut pending_inputs: ResMut<PendingInputs>,
+) {
+    if keyboard_input.any_pressed(THROTTLE_MODIFIER_KEYS) {
+        for event in mouse_wheel.read() {
+            control.limit = (control.limit + event.y.signum() * THROTTLE_LIMIT_STEP).clamp(THROTTLE_LIMIT_STEP, 1.0);
+        }
+    } else {
+        mouse_wheel.clear();
+    }
+
+    // the key pressed last wins, when it is released fall back to any other key that is still held
+    let just_pressed = THRUSTER_KEYS.iter().find(|(key, _)| keyboard_input.just_pressed(*key));
+    let held = THRUSTER_KEYS.iter().find(|(key, _)| keyboard_input.pressed(*key));
+    let key_direction = just_pressed.or(held).map(|(_, direction)| *direction);
+
+    // the longer a key is held the harder the thruster fires
+    if just_pressed.is_some() || key_direction != control.last.0 {
+        control.held_for = 0.0;
+    }
+    control.held_for += time.delta_secs();
+    let from_keys = key_direction.map(|direction| {
+        (direction, control.limit * (control.held_for / THROTTLE_RAMP_SECONDS).min(1.0))
+    });
+
+    // the right trigger slows down and the left one speeds up, as far as they are pressed
+    let from_gamepad = gamepads.iter().find_map(|gamepad| {
+        [
+            (GamepadButton::RightTrigger2, ThrusterDirection::Retrograde),
+            (GamepadButton::LeftTrigger2, ThrusterDirection::Prograde),
+        ]
+        .into_iter()
+        .map(|(trigger, direction)| (direction, gamepad.get(trigger).unwrap_or_default()))
+        .find(|(_, value)| *value > TRIGGER_DEADZONE)
+    });
+
+    let input = match from_keys.or(from_gamepad) {
+        Some((direction, throttle)) => (
+            Some(direction),
+            ((throttle / THROTTLE_STEP).round() * THROTTLE_STEP).clamp(THROTTLE_STEP, 1.0),
+        ),
+        None => (None, 0.0),
+    };
+    if input != control.last {
+        control.last = input;
+        pending_inputs.push(PlayerInput::Thruster {
+            direction: input.0,
+            throttle: input.1,
+        });
+    }
+}
+
+/// Only the thruster of the selected collector is fired by the player
+pub fn apply_thruster_input(
+    input: On<ApplyPlayerInput>,
+    mut query: Query<(&mut Thruster, Option<&Name>), With<NavigationInstruments>>,
+) {
+    let PlayerInput::Thruster { direction, throttle } = input.0 else {
+        return;
+    };
+
     query.iter_mut().for_each(|(mut thruster, name)| {
         info!(
-            "Toggling thruster of {} {}",
+            "Turning thruster of {} {} at {:.0}%",
             match name {
                 Some(name) => name.as_str(),
                 None => "unknown",
             },
-            if thruster.active { "off" } else { "on" },
+            match direction {
+                Some(direction) => direction.name(),
+                None => "off",
+            },
+            throttle * 100.0,
         );
 
-        thruster.active = !thruster.active;
+        thruster.active = direction.is_some() && throttle > 0.0;
+        thruster.throttle = throttle.clamp(0.0, 1.0);
+        if let Some(direction) = direction {
+            thruster.direction = direction;
+        }
     })
 }
 
 pub fn apply_thrust_force(
-    mut query: Query<(&Thruster, &Velocity, &Transform, &mut ThrustForce)>,
-    attractor: Query<&Transform, With<Attractor>>,
+    mut query: Query<(Entity, &Thruster, &Velocity, &Transform, &mut ThrustForce)>,
+    attractor: Query<GravitySourceData, With<Attractor>>,
 ) {
-    let attractor = attractor
-        .single()
-        .expect("Cannot use thrusters in worlds with other than exactly one attractor");
+    let sources = collect_gravity_sources(attractor.iter());
 
     query
         .iter_mut()
-        .for_each(|(i_thruster, i_velocity, i_trans, mut i_thrust_force)| {
+        .for_each(|(i_entity, i_thruster, i_velocity, i_trans, mut i_thrust_force)| {
             if i_thruster.active {
-                let direction = match i_thruster.direction {
-                    ThrusterDirection::Prograde => i_velocity.0,
-                    ThrusterDirection::Retrograde => i_velocity.0.neg(),
-                    ThrusterDirection::RadialIn => {
-                        let center = attractor.translation.xy();
-                        let i_pos = i_trans.translation.xy();
-                        let relative_position = i_pos - center;
-                        let rotation = if relative_position.angle_to(i_velocity.0) < 0.0 {
-                            -1.0
-                        } else {
-                            1.0
-                        };
-                        (relative_position.perp() * rotation).neg()
-                    },
-                    ThrusterDirection::RadialOut => {
-                        let center = attractor.translation.xy();
-                        let i_pos = i_trans.translation.xy();
-                        let offset = i_pos - center;
-                        let rotation = if offset.angle_to(i_velocity.0) < 0.0 {
-                            -1.0
-                        } else {
-                            1.0
-                        };
-                        offset.perp() * rotation
-                    }
+                let Some(direction) = calc_thrust_direction_around(&sources, i_entity, i_thruster.direction, i_velocity.0, i_trans.translation.xy()) else {
+                    return;
                 };
 
-                i_thrust_force.0 = direction.clamp_length(1.0, 1.0) * i_thruster.strength;
+                i_thrust_force.0 =
+                    direction.clamp_length(1.0, 1.0) * i_thruster.strength * i_thruster.throttle * i_thruster.direction.power();
             }
         });
 }
 
+/// Show in which direction the selected collector is burning
+pub fn draw_thrust_direction(
+    mut gizmos: Gizmos,
+    query: Query<(Entity, &Thruster, &Velocity, &Transform)>,
+    attractor: Query<GravitySourceData, With<Attractor>>,
+) {
+    let sources = collect_gravity_sources(attractor.iter());
+
+    query.iter().filter(|(_, thruster, ..)| thruster.active).for_each(|(i_entity, i_thruster, i_velocity, i_trans)| {
+        let i_pos = i_trans.translation.xy();
+        let Some(direction) = calc_thrust_direction_around(&sources, i_entity, i_thruster.direction, i_velocity.0, i_pos) else {
+            return;
+        };
+
+        // the arrow grows with the thrust
+        let thrust = i_thruster.throttle * i_thruster.direction.power();
+        gizmos.arrow_2d(i_pos, i_pos + direction.normalize_or_zero() * (5.0 + 10.0 * thrust), ORANGE);
+    });
+}
+
+/// Calculate the direction in which a thruster pushes relative to the body that is orbited at `position`
+fn calc_thrust_direction_around(sources: &[GravitySource], entity: Entity, direction: ThrusterDirection, velocity: Vec2, position: Vec2) -> Option<Vec2> {
+    let reference = dominant_body(sources, Some(entity), position);
+    // directions are relative to the orbited body, which might be moving itself
+    let velocity = velocity - reference.and_then(|body| body.velocity).unwrap_or_default();
+    let center = reference.map(|body| body.position);
+    calc_thrust_direction(direction, velocity, position, center)
+}
+
+/// Calculate the direction in which a thruster pushes.
+/// Radial directions are relative to `center` (the dominant body) and cannot be calculated without one.
+/// They point straight towards or away from it, so that radial burns don't change the speed around it.
+pub fn calc_thrust_direction(direction: ThrusterDirection, velocity: Vec2, position: Vec2, center: Option<Vec2>) -> Option<Vec2> {
+    match direction {
+        ThrusterDirection::Prograde => Some(velocity),
+        ThrusterDirection::Retrograde => Some(velocity.neg()),
+        ThrusterDirection::RadialIn => Some((position - center?).normalize_or_zero().neg()),
+        ThrusterDirection::RadialOut => Some((position - center?).normalize_or_zero()),
+    }
+}
+
+/// Burns fuel while the thruster is firing, as much as the throttle is opened and the direction fires with
 pub fn thruster_use_fuel(mut thruster_query: Query<(&mut Thruster, &mut Fuel)>, time: Res<Time>) {
     for (mut thruster, mut fuel) in thruster_query.iter_mut() {
         if thruster.active && fuel.amount <= 0.0 {
             thruster.active = false;
         } else if thruster.active && fuel.amount > 0.0 {
-            fuel.amount -= time.delta_secs();
+            fuel.amount -= time.delta_secs() * thruster.throttle * thruster.direction.power();
             if fuel.amount < 0.0 {
                 fuel.amount = 0.0;
             }
         }
     }
 }
+
+/// Collectors whose level changes get a thruster of matching strength
+pub fn update_thruster_strength(mut query: Query<(&Level, &mut Thruster), Changed<Level>>) {
+    for (level, mut thruster) in query.iter_mut() {
+        thruster.strength = thruster_strength(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radial_directions_point_away_from_and_towards_the_center() {
+        // on a circular orbit around the origin, counter clockwise
+        let position = Vec2::new(10.0, 0.0);
+        let velocity = Vec2::new(0.0, 3.0);
+        let center = Some(Vec2::ZERO);
+
+        let out = calc_thrust_direction(ThrusterDirection::RadialOut, velocity, position, center).unwrap();
+        assert!(out.distance(Vec2::X) < 1e-6, "radial out is {out}");
+        let inwards = calc_thrust_direction(ThrusterDirection::RadialIn, velocity, position, center).unwrap();
+        assert!(inwards.distance(Vec2::NEG_X) < 1e-6, "radial in is {inwards}");
+
+        // the same when orbiting clockwise
+        let out = calc_thrust_direction(ThrusterDirection::RadialOut, -velocity, position, center).unwrap();
+        assert!(out.distance(Vec2::X) < 1e-6, "radial out is {out}");
+    }
+
+    #[test]
+    fn radial_directions_do_not_depend_on_the_velocity() {
+        let center = Vec2::new(1.0, 1.0);
+        let position = Vec2::new(7.0, -7.0);
+        let expected = (position - center).normalize();
+
+        // also when already moving away from the center on an eccentric orbit
+        for velocity in [Vec2::new(2.0, 5.0), Vec2::new(6.0, -1.0)] {
+            let out = calc_thrust_direction(ThrusterDirection::RadialOut, velocity, position, Some(center)).unwrap();
+            assert!(out.distance(expected) < 1e-6, "radial out is {out}");
+            let inwards = calc_thrust_direction(ThrusterDirection::RadialIn, velocity, position, Some(center)).unwrap();
+            assert!(inwards.distance(-expected) < 1e-6, "radial in is {inwards}");
+        }
+    }
+
+    #[test]
+    fn radial_directions_need_a_center() {
+        assert_eq!(calc_thrust_direction(ThrusterDirection::RadialOut, Vec2::X, Vec2::ONE, None), None);
+        assert_eq!(
+            calc_thrust_direction(ThrusterDirection::Retrograde, Vec2::X, Vec2::ONE, None),
+            Some(Vec2::NEG_X)
+        );
+    }
+}
@@ -0,0 +1,1182 @@
+//! Runs the game simulation headless and launches or places collectors programmatically.
+
+use bevy::diagnostic::DiagnosticsStore;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use ldjam58::SimulationPlugin;
+use ldjam58::boundary::{LostToSpace, WorldBoundary};
+use ldjam58::collision::conjunction::{CONJUNCTION_SWEEPS, ConjunctionConfig, Conjunctions};
+use ldjam58::collision::damage::{DamageConfig, Health};
+use ldjam58::collision::debris::{Debris, DebrisConfig};
+use ldjam58::collision::layers::{CollisionLayer, CollisionRule, CollisionRules};
+use ldjam58::collision::shape::HitBoxShape;
+use ldjam58::collision::{DamageCollisionEvent, FatalCollisionEvent, GeneralCollisionEvent, HitBox};
+use ldjam58::launching::{CollectorId, CollectorStats, Fuel};
+use ldjam58::physics::calc_gravity::{Attractee, Attractor, GravityModel};
+use ldjam58::physics::directional_forces::Mass;
+use ldjam58::physics::integrator::{SimulationTimeDropped, SubstepConfig};
+use ldjam58::physics::orbital_elements::{OrbitalElements, circular_speed, escape_speed, gravitational_parameter};
+use ldjam58::physics::velocity::Velocity;
+use ldjam58::replay::{InputLog, PendingInputs, PlayerInput, ReplayOptions, SimulationClock};
+use ldjam58::score::Score;
+use ldjam58::screens::Screen;
+use ldjam58::sun_system::asteroids::{
+    Asteroid, AsteroidConfig, AsteroidDirector, AsteroidSwarm, AsteroidWave, LARGE_ASTEROID_SIZE, free_asteroid,
+    show_swarm_path,
+};
+use ldjam58::sun_system::autopilot::{Autopilot, AutopilotCommand, AutopilotError, AutopilotFailed};
+use ldjam58::sun_system::earth::{EARTH_MASS, Earth};
+use ldjam58::sun_system::maneuver::ManeuverNode;
+use ldjam58::sun_system::navigation_instruments::{
+    NavigationInstruments, OrbitProjection, ProjectionSettings, update_nav_projections,
+};
+use ldjam58::sun_system::thruster::{Thruster, ThrusterDirection};
+use ldjam58::sun_system::{Level, SUN_MASS, Satellite, Sun};
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// Simulation ticks per second, matching bevys default fixed timestep
+const TICKS_PER_SECOND: usize = 64;
+
+/// What destroyed collectors crashed into
+#[derive(Resource, Default)]
+struct FatalCollisions(Vec<Entity>);
+
+/// Why the autopilot gave up
+#[derive(Resource, Default)]
+struct AutopilotFailures(Vec<AutopilotError>);
+
+fn headless_app() -> App {
+    headless_app_with(SimulationPlugin {
+        replay: ReplayOptions {
+            seed: Some(1),
+            ..default()
+        },
+        ..default()
+    })
+}
+
+fn headless_app_with(simulation: SimulationPlugin) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins);
+    app.add_plugins(simulation);
+    // advance exactly one simulation tick per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / TICKS_PER_SECOND as f64,
+    )));
+    // keep asteroids out of the way so that they don't interfere with the scenarios
+    app.world_mut().resource_mut::<AsteroidConfig>().enabled = false;
+
+    app.init_resource::<FatalCollisions>();
+    app.add_observer(|event: On<FatalCollisionEvent>, mut collisions: ResMut<FatalCollisions>| {
+        collisions.0.push(event.other);
+    });
+
+    app.world_mut()
+        .resource_mut::<NextState<Screen>>()
+        .set(Screen::Gameplay);
+    app.update();
+    app
+}
+
+fn run_for(app: &mut App, seconds: f32) {
+    for _ in 0..(seconds * TICKS_PER_SECOND as f32) as usize {
+        app.update();
+    }
+}
+
+fn single<T: Component>(app: &mut App) -> Entity {
+    app.world_mut().query_filtered::<Entity, With<T>>().single(app.world()).unwrap()
+}
+
+fn earth(app: &mut App) -> (Vec2, Vec2) {
+    app.world_mut()
+        .query_filtered::<(&Transform, &Velocity), With<Earth>>()
+        .single(app.world())
+        .map(|(transform, velocity)| (transform.translation.xy(), velocity.0))
+        .unwrap()
+}
+
+fn earth_velocity(app: &mut App) -> Vec2 {
+    earth(app).1
+}
+
+/// Put a collector directly into the world instead of launching it from earth
+fn spawn_collector(app: &mut App, position: Vec2, velocity: Vec2) {
+    app.world_mut().spawn(collector_bundle(position, velocity));
+    run_for(app, 0.1);
+}
+
+fn collector_bundle(position: Vec2, velocity: Vec2) -> impl Bundle {
+    (
+        Satellite,
+        Attractee,
+        Level { level: 1.0 },
+        Mass(1.0),
+        Velocity(velocity),
+        HitBox {
+            shape: HitBoxShape::Circle { radius: 4.0 },
+            layer: CollisionLayer::Collector,
+            ..default()
+        },
+        CollectorStats {
+            energy_rate: 0.0,
+            total_collected: 0.0,
+        },
+        Transform::from_translation(position.extend(0.0)),
+    )
+}
+
+/// Launch a collector so that it ends up with the given velocity, taking into account that it inherits earths velocity
+fn launch(app: &mut App, velocity: Vec2) {
+    let relative = velocity - earth_velocity(app);
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Launch {
+        direction: relative.normalize(),
+        force: relative.length(),
+    });
+    run_for(app, 0.1);
+}
+
+fn collector(app: &mut App) -> Option<(Vec2, OrbitalElements)> {
+    app.world_mut()
+        .query_filtered::<(&Transform, &OrbitalElements), With<Satellite>>()
+        .iter(app.world())
+        .next()
+        .map(|(transform, elements)| (transform.translation.xy(), *elements))
+}
+
+#[test]
+fn collector_in_circular_orbit_stays_in_orbit() {
+    let mut app = headless_app();
+    // without the pull of the earth any drift of the orbit can only come from integrating it
+    let earth = single::<Earth>(&mut app);
+    app.world_mut().entity_mut(earth).remove::<Attractor>();
+
+    // on the opposite side of the sun than earth
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    let (_, initial) = collector(&mut app).expect("collector should have been spawned");
+    assert_eq!(initial.reference, Some(single::<Sun>(&mut app)));
+    assert!(initial.bound);
+    assert!(initial.eccentricity < 0.1, "orbit should be nearly circular but is {initial:?}");
+
+    // fly for roughly two full orbits
+    run_for(&mut app, 160.0);
+
+    let (position, elements) = collector(&mut app).expect("collector should still exist");
+    assert!(app.world().resource::<FatalCollisions>().0.is_empty());
+    assert!(elements.bound);
+    assert!((position.length() - 100.0).abs() < 10.0, "collector drifted to {position}");
+    let drift = ((elements.specific_energy - initial.specific_energy) / initial.specific_energy).abs();
+    assert!(drift < 1e-3, "orbital energy drifted by {drift}");
+}
+
+/// Physics ticks since the counter was last reset
+#[derive(Resource, Default)]
+struct PhysicsTicks(usize);
+
+/// Simulation time that physics could not keep up with
+#[derive(Resource, Default)]
+struct DroppedTime(f32);
+
+#[test]
+fn high_time_speed_runs_more_ticks_until_time_is_dropped() {
+    let mut app = headless_app();
+    app.init_resource::<PhysicsTicks>();
+    app.init_resource::<DroppedTime>();
+    app.add_systems(FixedUpdate, |mut ticks: ResMut<PhysicsTicks>| ticks.0 += 1);
+    app.add_observer(|event: On<SimulationTimeDropped>, mut dropped: ResMut<DroppedTime>| {
+        dropped.0 += event.dropped;
+    });
+    let config = *app.world().resource::<SubstepConfig>();
+
+    // a single frame at the given time speed
+    let mut frame = |speed: f32| {
+        app.world_mut().resource_mut::<Time<Virtual>>().set_relative_speed(speed);
+        app.world_mut().resource_mut::<PhysicsTicks>().0 = 0;
+        app.world_mut().resource_mut::<DroppedTime>().0 = 0.0;
+        app.update();
+        (app.world().resource::<PhysicsTicks>().0, app.world().resource::<DroppedTime>().0)
+    };
+
+    assert_eq!(frame(1.0), (1, 0.0));
+    // every frame is simulated in ticks of the same length, there are just more of them
+    assert_eq!(frame(20.0), (20, 0.0));
+
+    // beyond what may be simulated per frame the rest is dropped and reported
+    let speed = 4.0 * config.max_substeps as f32;
+    let (ticks, dropped) = frame(speed);
+    assert_eq!(ticks, config.max_substeps as usize);
+    let expected = speed / TICKS_PER_SECOND as f32 - config.max_frame_time();
+    assert!((dropped - expected).abs() < 1e-3, "dropped {dropped}s instead of {expected}s");
+}
+
+#[test]
+fn collector_launched_into_the_sun_is_destroyed() {
+    let mut app = headless_app();
+
+    // straight towards the sun, earth starts to the right of it
+    launch(&mut app, Vec2::NEG_X * 10.0);
+    assert!(collector(&mut app).is_some());
+
+    run_for(&mut app, 15.0);
+
+    assert!(collector(&mut app).is_none(), "collector should have crashed into the sun");
+    let sun = single::<Sun>(&mut app);
+    assert_eq!(app.world().resource::<FatalCollisions>().0, vec![sun]);
+}
+
+#[test]
+fn collector_parked_in_earth_orbit_stays_with_earth() {
+    // with n-body gravity the sun pulls collectors this close to earth out of their orbit, so the game uses
+    // patched conics
+    let mut app = headless_app_with(SimulationPlugin {
+        replay: ReplayOptions {
+            seed: Some(1),
+            ..default()
+        },
+        gravity: GravityModel::PatchedConics,
+    });
+
+    let (earth_position, earth_velocity) = earth(&mut app);
+    // just clear of earths surface
+    let speed = circular_speed(gravitational_parameter(&Mass(EARTH_MASS)), 10.0);
+    spawn_collector(&mut app, earth_position + Vec2::new(10.0, 0.0), earth_velocity + Vec2::new(0.0, speed));
+
+    // earth travels a good part around the sun and takes the collector with it
+    run_for(&mut app, 30.0);
+
+    let (position, elements) = collector(&mut app).expect("collector should still exist");
+    let (earth_position, _) = earth(&mut app);
+    assert!(app.world().resource::<FatalCollisions>().0.is_empty());
+    assert_eq!(elements.reference, Some(single::<Earth>(&mut app)));
+    assert!(elements.bound);
+    let distance = position.distance(earth_position);
+    assert!((distance - 10.0).abs() < 1.0, "collector drifted to a distance of {distance} from earth");
+}
+
+#[test]
+fn weak_launch_falls_back_onto_earth() {
+    let mut app = headless_app();
+
+    // far below the escape velocity of earth
+    let velocity = earth_velocity(&mut app) + Vec2::X;
+    launch(&mut app, velocity);
+    assert!(collector(&mut app).is_some());
+
+    run_for(&mut app, 10.0);
+
+    assert!(collector(&mut app).is_none(), "collector should have crashed into earth");
+    let earth = single::<Earth>(&mut app);
+    assert_eq!(app.world().resource::<FatalCollisions>().0, vec![earth]);
+}
+
+#[test]
+fn collectors_accrue_energy() {
+    let mut app = headless_app();
+    run_for(&mut app, 1.0);
+    let idle_rate = app.world().resource::<Score>().energy_rate;
+
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    let stored_after_launch = app.world().resource::<Score>().energy_stored;
+    run_for(&mut app, 10.0);
+
+    let score = app.world().resource::<Score>();
+    assert!(score.energy_rate > idle_rate, "rate {} should exceed idle rate {idle_rate}", score.energy_rate);
+    assert!(score.energy_stored > stored_after_launch);
+}
+
+/// Where every collector is by its id, the energy collected and the number of asteroids around
+fn outcome(app: &mut App) -> (Vec<(u32, Vec2)>, f32, usize) {
+    let mut collectors: Vec<(u32, Vec2)> = app
+        .world_mut()
+        .query::<(&CollectorId, &Transform)>()
+        .iter(app.world())
+        .map(|(id, transform)| (id.0, transform.translation.xy()))
+        .collect();
+    collectors.sort_by_key(|(id, _)| *id);
+    let asteroids = app.world_mut().query::<&Asteroid>().iter(app.world()).count();
+    (collectors, app.world().resource::<Score>().energy_stored, asteroids)
+}
+
+#[test]
+fn replayed_session_ends_exactly_like_the_recording() {
+    let options = ReplayOptions {
+        seed: Some(7),
+        ..default()
+    };
+    // asteroids are the main source of randomness, where swarms come from and how many asteroids they have
+    let send_asteroids = |app: &mut App| {
+        let mut config = app.world_mut().resource_mut::<AsteroidConfig>();
+        config.enabled = true;
+        config.waves = vec![AsteroidWave { at: 3.0, swarms: 3, asteroids: 2..6 }];
+    };
+    let mut recording = headless_app_with(SimulationPlugin {
+        replay: options.clone(),
+        ..default()
+    });
+    send_asteroids(&mut recording);
+
+    let earth_velocity = earth_velocity(&mut recording);
+    launch(&mut recording, earth_velocity + Vec2::new(8.0, 0.0));
+    run_for(&mut recording, 2.0);
+    launch(&mut recording, earth_velocity + Vec2::new(7.0, 4.0));
+    let pending = |app: &mut App, input| app.world_mut().resource_mut::<PendingInputs>().push(input);
+    pending(&mut recording, PlayerInput::Select { collector: 0 });
+    pending(
+        &mut recording,
+        PlayerInput::Thruster {
+            direction: Some(ThrusterDirection::Prograde),
+            throttle: 0.5,
+        },
+    );
+    run_for(&mut recording, 1.0);
+    pending(
+        &mut recording,
+        PlayerInput::Thruster {
+            direction: None,
+            throttle: 0.0,
+        },
+    );
+    pending(&mut recording, PlayerInput::TimeSpeed { speed: 4.0 });
+    run_for(&mut recording, 10.0);
+
+    let log = recording.world().resource::<InputLog>().clone();
+    assert_eq!(log.inputs.len(), 6);
+    let path = std::env::temp_dir().join(format!("ldjam58-replay-{}.txt", std::process::id()));
+    std::fs::write(&path, log.to_text()).unwrap();
+
+    let mut replay = headless_app_with(SimulationPlugin {
+        replay: ReplayOptions {
+            replay: Some(path.clone()),
+            ..options
+        },
+        ..default()
+    });
+    send_asteroids(&mut replay);
+    let ticks = recording.world().resource::<SimulationClock>().tick;
+    while replay.world().resource::<SimulationClock>().tick < ticks {
+        replay.update();
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    let expected = outcome(&mut recording);
+    assert!(!expected.0.is_empty(), "the collectors should still be around");
+    assert!(expected.2 > 0, "asteroids should have arrived");
+    assert_eq!(replay.world().resource::<SimulationClock>().tick, ticks);
+    assert_eq!(outcome(&mut replay), expected);
+}
+
+#[test]
+fn planned_maneuver_is_burned_at_the_node() {
+    let mut app = headless_app();
+
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    let (_, initial) = collector(&mut app).unwrap();
+
+    let now = app.world().resource::<SimulationClock>().elapsed;
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::PlanManeuver {
+        at: now + 2.0,
+        prograde: 1.0,
+        radial: 0.0,
+    });
+    run_for(&mut app, 1.0);
+    assert!(app.world().get::<ManeuverNode>(satellite).is_some(), "burn should not have started yet");
+    assert_eq!(app.world().get::<Fuel>(satellite).unwrap().amount, 1.5);
+
+    run_for(&mut app, 2.0);
+    assert!(app.world().get::<ManeuverNode>(satellite).is_none(), "burn should be finished");
+    // a delta-v of 1 takes half a second at full thrust
+    let fuel = app.world().get::<Fuel>(satellite).unwrap().amount;
+    assert!((fuel - 1.0).abs() < 0.01, "burn used {} fuel", 1.5 - fuel);
+
+    // burning prograde on a circular orbit raises its energy by about v * dv
+    let (_, elements) = collector(&mut app).unwrap();
+    let gained = elements.specific_energy - initial.specific_energy;
+    assert!((gained - speed).abs() < 0.1 * speed, "energy changed by {gained} instead of {speed}");
+}
+
+/// Put a collector on a circular orbit around the sun, opposite of earth, and select it
+fn spawn_selected_collector(app: &mut App, fuel: f32) -> Entity {
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    spawn_collector(app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    let satellite = single::<Satellite>(app);
+    app.world_mut().entity_mut(satellite).insert((
+        NavigationInstruments,
+        Thruster::new(ThrusterDirection::Retrograde, 2.0),
+        Fuel { amount: fuel },
+    ));
+    satellite
+}
+
+/// Give the selected collector an id and put another one on a wider orbit, which can be selected instead
+fn spawn_second_collector(app: &mut App, selected: Entity) {
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 130.0);
+    app.world_mut().entity_mut(selected).insert(CollectorId(0));
+    app.world_mut()
+        .spawn((collector_bundle(Vec2::new(0.0, -130.0), Vec2::new(speed, 0.0)), CollectorId(1)));
+}
+
+#[test]
+fn planned_maneuver_is_burned_after_selecting_another_collector() {
+    let mut app = headless_app();
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    spawn_second_collector(&mut app, satellite);
+
+    let now = app.world().resource::<SimulationClock>().elapsed;
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::PlanManeuver {
+        at: now + 2.0,
+        prograde: 1.0,
+        radial: 0.0,
+    });
+    run_for(&mut app, 1.0);
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Select { collector: 1 });
+    run_for(&mut app, 0.1);
+    assert!(app.world().get::<NavigationInstruments>(satellite).is_none());
+    assert!(app.world().get::<ManeuverNode>(satellite).is_some(), "node should be kept");
+
+    run_for(&mut app, 2.0);
+    assert!(app.world().get::<ManeuverNode>(satellite).is_none(), "burn should be finished");
+    let fuel = app.world().get::<Fuel>(satellite).unwrap().amount;
+    assert!((fuel - 1.0).abs() < 0.01, "burn used {} fuel", 1.5 - fuel);
+}
+
+/// Keep the projections up to date like the game does, right after each simulation tick
+fn update_projections(app: &mut App, settings: ProjectionSettings) {
+    app.insert_resource(settings);
+    app.add_systems(Update, update_nav_projections);
+}
+
+fn computed_at(app: &App, entity: Entity) -> Option<f32> {
+    app.world().get::<OrbitProjection>(entity).unwrap().computed_at()
+}
+
+#[test]
+fn projection_is_reused_while_the_collector_follows_it() {
+    let mut app = headless_app();
+    update_projections(&mut app, ProjectionSettings::default());
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    run_for(&mut app, 0.1);
+    let computed = computed_at(&app, satellite).expect("projection should have been computed");
+
+    run_for(&mut app, 1.5);
+    assert_eq!(computed_at(&app, satellite), Some(computed));
+
+    // even a perfectly followed projection is only trusted for a while
+    run_for(&mut app, 1.0);
+    assert!(computed_at(&app, satellite) > Some(computed));
+}
+
+#[test]
+fn projection_is_recomputed_when_the_collector_strays_from_it() {
+    let mut app = headless_app();
+    update_projections(&mut app, ProjectionSettings::default());
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    run_for(&mut app, 0.1);
+    let computed = computed_at(&app, satellite);
+
+    // a bit closer than the tolerance is still fine
+    app.world_mut().get_mut::<Transform>(satellite).unwrap().translation.x -= 0.2;
+    run_for(&mut app, 0.1);
+    assert_eq!(computed_at(&app, satellite), computed);
+
+    app.world_mut().get_mut::<Transform>(satellite).unwrap().translation.x -= 1.0;
+    run_for(&mut app, 0.1);
+    assert!(computed_at(&app, satellite) > computed);
+}
+
+#[test]
+fn projection_is_recomputed_while_thrusting() {
+    let mut app = headless_app();
+    update_projections(&mut app, ProjectionSettings::default());
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    run_for(&mut app, 0.1);
+    let computed = computed_at(&app, satellite);
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Thruster {
+        direction: Some(ThrusterDirection::Prograde),
+        throttle: 0.5,
+    });
+    run_for(&mut app, 0.1);
+    let thrusting = computed_at(&app, satellite);
+    assert!(thrusting > computed);
+    run_for(&mut app, 0.1);
+    assert!(computed_at(&app, satellite) > thrusting, "every frame of the burn changes the path");
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Thruster {
+        direction: None,
+        throttle: 0.0,
+    });
+    run_for(&mut app, 0.1);
+    let after_burn = computed_at(&app, satellite);
+    run_for(&mut app, 0.5);
+    assert_eq!(computed_at(&app, satellite), after_burn);
+}
+
+#[test]
+fn projection_is_recomputed_when_the_maneuver_changes() {
+    let mut app = headless_app();
+    update_projections(&mut app, ProjectionSettings::default());
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    run_for(&mut app, 0.1);
+    let computed = computed_at(&app, satellite);
+
+    let now = app.world().resource::<SimulationClock>().elapsed;
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::PlanManeuver {
+        at: now + 20.0,
+        prograde: 1.0,
+        radial: 0.0,
+    });
+    run_for(&mut app, 0.1);
+    let planned = computed_at(&app, satellite);
+    assert!(planned > computed);
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::PlanManeuver {
+        at: now + 20.0,
+        prograde: 0.5,
+        radial: 0.0,
+    });
+    run_for(&mut app, 0.1);
+    assert!(computed_at(&app, satellite) > planned);
+}
+
+#[test]
+fn projection_updates_are_limited_per_frame_with_the_selected_collector_first() {
+    let mut app = headless_app();
+    update_projections(
+        &mut app,
+        ProjectionSettings {
+            show_all: true,
+            max_updates_per_frame: 1,
+        },
+    );
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let others = [Vec2::new(0.0, 100.0), Vec2::new(0.0, -100.0)].map(|position| {
+        app.world_mut()
+            .spawn((collector_bundle(position, position.perp().normalize() * speed), OrbitProjection::default()))
+            .id()
+    });
+    let satellite = app
+        .world_mut()
+        .spawn((collector_bundle(Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed)), NavigationInstruments))
+        .id();
+
+    let computed = |app: &App| [satellite, others[0], others[1]].map(|entity| computed_at(app, entity).is_some());
+    app.update();
+    assert_eq!(computed(&app), [true, false, false]);
+    app.update();
+    assert_eq!(computed(&app).iter().filter(|computed| **computed).count(), 2);
+    app.update();
+    assert_eq!(computed(&app), [true, true, true]);
+}
+
+#[test]
+fn autopilot_transfers_to_a_higher_circular_orbit() {
+    let mut app = headless_app();
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Autopilot {
+        command: Some(AutopilotCommand::HohmannTransfer { radius: 110.0 }),
+    });
+    // the transfer takes half an orbit of about 83 seconds
+    run_for(&mut app, 50.0);
+
+    assert!(app.world().get::<Autopilot>(satellite).is_none(), "autopilot should be done");
+    let (_, elements) = collector(&mut app).unwrap();
+    assert!(elements.eccentricity < 0.02, "orbit should be circular but is {elements:?}");
+    assert!((elements.semi_major_axis - 110.0).abs() < 1.0, "orbit should be at 110 but is {elements:?}");
+    // two burns of about 0.19 delta-v each
+    let fuel = app.world().get::<Fuel>(satellite).unwrap().amount;
+    assert!((1.5 - fuel - 0.19).abs() < 0.02, "transfer used {} fuel", 1.5 - fuel);
+}
+
+#[test]
+fn autopilot_finishes_the_transfer_after_selecting_another_collector() {
+    let mut app = headless_app();
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    spawn_second_collector(&mut app, satellite);
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Autopilot {
+        command: Some(AutopilotCommand::HohmannTransfer { radius: 110.0 }),
+    });
+    // between the burn at the periapsis and the one at the apoapsis
+    run_for(&mut app, 10.0);
+    let fuel = app.world().get::<Fuel>(satellite).unwrap().amount;
+    assert!(fuel < 1.5, "first burn should be done");
+    assert!(app.world().get::<Autopilot>(satellite).is_some());
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Select { collector: 1 });
+    run_for(&mut app, 40.0);
+
+    assert!(app.world().get::<NavigationInstruments>(satellite).is_none());
+    assert!(app.world().get::<Autopilot>(satellite).is_none(), "autopilot should be done");
+    let elements = *app.world().get::<OrbitalElements>(satellite).unwrap();
+    assert!(elements.eccentricity < 0.02, "orbit should be circular but is {elements:?}");
+    assert!((elements.semi_major_axis - 110.0).abs() < 1.0, "orbit should be at 110 but is {elements:?}");
+}
+
+#[test]
+fn autopilot_fails_without_enough_fuel() {
+    let mut app = headless_app();
+    let satellite = spawn_selected_collector(&mut app, 0.05);
+
+    app.init_resource::<AutopilotFailures>();
+    app.add_observer(|event: On<AutopilotFailed>, mut failures: ResMut<AutopilotFailures>| {
+        failures.0.push(event.error);
+    });
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Autopilot {
+        command: Some(AutopilotCommand::HohmannTransfer { radius: 110.0 }),
+    });
+    run_for(&mut app, 1.0);
+
+    let failures = &app.world().resource::<AutopilotFailures>().0;
+    assert!(
+        matches!(failures.as_slice(), [AutopilotError::InsufficientFuel { .. }]),
+        "unexpected failures {failures:?}"
+    );
+    assert!(app.world().get::<Autopilot>(satellite).is_none());
+    assert_eq!(app.world().get::<Fuel>(satellite).unwrap().amount, 0.05);
+}
+
+#[test]
+fn throttle_scales_thrust_and_fuel_use() {
+    let mut app = headless_app();
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    let (_, initial) = collector(&mut app).unwrap();
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Thruster {
+        direction: Some(ThrusterDirection::Prograde),
+        throttle: 0.5,
+    });
+    run_for(&mut app, 1.0);
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Thruster {
+        direction: None,
+        throttle: 0.0,
+    });
+    run_for(&mut app, 0.1);
+
+    // half of the fuel and half of the delta-v of a second at full throttle
+    let fuel = app.world().get::<Fuel>(satellite).unwrap().amount;
+    assert!((1.5 - fuel - 0.5).abs() < 0.02, "burned {} fuel", 1.5 - fuel);
+    let (_, elements) = collector(&mut app).unwrap();
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let gained = elements.specific_energy - initial.specific_energy;
+    assert!((gained - speed).abs() < 0.1 * speed, "energy changed by {gained} instead of {speed}");
+}
+
+#[test]
+fn radial_thrust_keeps_the_speed_along_the_orbit() {
+    let mut app = headless_app();
+    let satellite = spawn_selected_collector(&mut app, 1.5);
+    let angular_momentum = |app: &mut App| {
+        let position = app.world().get::<Transform>(satellite).unwrap().translation.xy();
+        let velocity = app.world().get::<Velocity>(satellite).unwrap().0;
+        (position.perp_dot(velocity), position.normalize().dot(velocity))
+    };
+    let (initial, _) = angular_momentum(&mut app);
+
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Thruster {
+        direction: Some(ThrusterDirection::RadialOut),
+        throttle: 1.0,
+    });
+    run_for(&mut app, 1.0);
+    app.world_mut().resource_mut::<PendingInputs>().push(PlayerInput::Thruster {
+        direction: None,
+        throttle: 0.0,
+    });
+    run_for(&mut app, 0.1);
+
+    // pushing straight away from the sun doesn't turn the collector around it any faster or slower
+    let (angular_momentum, radial_speed) = angular_momentum(&mut app);
+    assert!(
+        (angular_momentum - initial).abs() < 1e-3 * initial.abs(),
+        "angular momentum changed from {initial} to {angular_momentum}"
+    );
+    // the side thrusters push with half the strength and burn half the fuel of the main engine
+    assert!((radial_speed - 1.0).abs() < 0.1, "moving away from the sun at {radial_speed}");
+    let fuel = app.world().get::<Fuel>(satellite).unwrap().amount;
+    assert!((1.5 - fuel - 0.5).abs() < 0.02, "burned {} fuel", 1.5 - fuel);
+}
+
+#[test]
+fn collision_between_collectors_is_predicted() {
+    let mut app = headless_app();
+
+    // two collectors on the same orbit flying towards each other, they meet after about 10 seconds
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let angle = 2.0 * 10.0 * speed / 100.0;
+    let start = Vec2::from_angle(PI + angle);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    spawn_collector(&mut app, start * 100.0, -start.perp() * speed);
+    run_for(&mut app, 1.0);
+
+    let conjunctions = &app.world().resource::<Conjunctions>().0;
+    assert_eq!(conjunctions.len(), 1, "expected one conjunction but got {conjunctions:?}");
+    let in_seconds = conjunctions[0].in_seconds;
+    assert!((in_seconds - 8.8).abs() < 1.0, "collision predicted in {in_seconds}s");
+    assert!(app.world().resource::<FatalCollisions>().0.is_empty());
+
+    run_for(&mut app, in_seconds + 1.0);
+    assert_eq!(app.world().resource::<FatalCollisions>().0.len(), 2, "both collectors should have crashed");
+}
+
+#[test]
+fn collisions_are_only_predicted_for_the_budgeted_collectors() {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<ConjunctionConfig>().max_collectors = 1;
+
+    // the same two collectors flying towards each other and a selected one far away from both
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let angle = 2.0 * 10.0 * speed / 100.0;
+    let start = Vec2::from_angle(PI + angle);
+    app.world_mut().spawn(collector_bundle(Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed)));
+    app.world_mut().spawn(collector_bundle(start * 100.0, -start.perp() * speed));
+    let far_speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 140.0);
+    let selected = app
+        .world_mut()
+        .spawn((collector_bundle(Vec2::new(0.0, 140.0), Vec2::new(far_speed, 0.0)), NavigationInstruments))
+        .id();
+    run_for(&mut app, 1.0);
+
+    // only the selected collector is checked and nothing comes close enough to it to be swept
+    assert!(app.world().resource::<Conjunctions>().0.is_empty());
+    let sweeps = app.world().resource::<DiagnosticsStore>().get(&CONJUNCTION_SWEEPS).and_then(|diagnostic| diagnostic.value());
+    assert_eq!(sweeps, Some(0.0));
+
+    app.world_mut().resource_mut::<ConjunctionConfig>().max_collectors = 2;
+    run_for(&mut app, 1.0);
+    let conjunctions = &app.world().resource::<Conjunctions>().0;
+    assert_eq!(conjunctions.len(), 1, "expected one conjunction but got {conjunctions:?}");
+    assert!(conjunctions[0].a != selected && conjunctions[0].b != selected);
+}
+
+#[test]
+fn asteroid_swarm_flies_by_the_sun() {
+    let mut app = headless_app();
+    let mut config = app.world_mut().resource_mut::<AsteroidConfig>();
+    config.enabled = true;
+    config.waves = vec![AsteroidWave { at: 1.0, swarms: 1, asteroids: 3..4 }];
+    run_for(&mut app, 1.5);
+
+    let swarm = single::<AsteroidSwarm>(&mut app);
+    let sun = single::<Sun>(&mut app);
+    let spawn_distance = app.world().resource::<AsteroidConfig>().spawn_distance;
+    let (position, velocity, elements) = app
+        .world_mut()
+        .query::<(&Transform, &Velocity, &OrbitalElements)>()
+        .get(app.world(), swarm)
+        .map(|(transform, velocity, elements)| (transform.translation.xy(), velocity.0, *elements))
+        .unwrap();
+    assert!((position.length() - spawn_distance).abs() < 20.0, "swarm appeared at {position}");
+    assert!(velocity.dot(position) < 0.0, "swarm should fly inwards");
+    assert_eq!(elements.reference, Some(sun));
+    assert!(elements.periapsis > 20.0 && elements.periapsis < 160.0, "swarm passes the sun at {}", elements.periapsis);
+
+    // it speeds up while falling towards the sun
+    run_for(&mut app, 5.0);
+    let faster = app.world().get::<Velocity>(swarm).unwrap().0;
+    assert!(faster.length() > velocity.length(), "swarm slowed down from {velocity} to {faster}");
+}
+
+#[test]
+fn path_of_a_new_asteroid_swarm_is_projected() {
+    let mut app = headless_app();
+    update_projections(&mut app, ProjectionSettings::default());
+    app.add_observer(show_swarm_path);
+    let mut config = app.world_mut().resource_mut::<AsteroidConfig>();
+    config.enabled = true;
+    config.waves = vec![AsteroidWave { at: 1.0, swarms: 1, asteroids: 3..4 }];
+    run_for(&mut app, 1.5);
+
+    let swarm = single::<AsteroidSwarm>(&mut app);
+    let projection = app.world().get::<OrbitProjection>(swarm).expect("swarm path should be shown");
+    assert!(projection.len() > 1, "swarm path should have been projected");
+}
+
+#[test]
+fn struck_asteroid_breaks_into_fragments() {
+    let mut app = headless_app();
+
+    // a collector on a circular orbit and a large asteroid coming straight at it, they meet after about a second
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let asteroid = app
+        .world_mut()
+        .spawn(free_asteroid(LARGE_ASTEROID_SIZE, Vec2::new(-100.0, -20.0), Quat::IDENTITY, Vec2::new(0.0, speed)))
+        .id();
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    run_for(&mut app, 2.0);
+
+    assert!(app.world().get_entity(asteroid).is_err(), "the struck asteroid should be gone");
+    assert_eq!(app.world().resource::<FatalCollisions>().0.len(), 2, "collector and asteroid should have crashed");
+    let fragments = app
+        .world_mut()
+        .query::<(&Asteroid, &Velocity)>()
+        .iter(app.world())
+        .map(|(asteroid, velocity)| (asteroid.size, velocity.0))
+        .collect::<Vec<_>>();
+    assert_eq!(fragments.len(), 2, "expected two fragments but got {fragments:?}");
+    for (size, velocity) in fragments {
+        assert_eq!(size, LARGE_ASTEROID_SIZE - 1);
+        // the fragments keep flying in the direction of the asteroid, slowed down by the collector it knocked away
+        assert!(velocity.y > 0.0 && velocity.y < speed, "fragment flies with {velocity}");
+    }
+}
+
+#[test]
+fn asteroid_waves_arrive_as_forecast() {
+    let mut app = headless_app();
+    let mut config = app.world_mut().resource_mut::<AsteroidConfig>();
+    config.enabled = true;
+    config.waves = vec![AsteroidWave { at: 5.0, swarms: 2, asteroids: 2..3 }];
+    run_for(&mut app, 1.0);
+
+    let forecast = |app: &mut App| {
+        let now = app.world().resource::<SimulationClock>().elapsed;
+        app.world()
+            .resource::<AsteroidDirector>()
+            .forecast(now)
+            .map(|(in_seconds, wave)| (in_seconds, wave.clone()))
+            .unwrap()
+    };
+    let (in_seconds, wave) = forecast(&mut app);
+    assert!((in_seconds - 4.0).abs() < 0.1, "wave forecast in {in_seconds}s");
+    assert_eq!(wave.swarms, 2);
+    assert_eq!(app.world_mut().query::<&AsteroidSwarm>().iter(app.world()).count(), 0);
+
+    run_for(&mut app, 4.5);
+    assert_eq!(app.world_mut().query::<&AsteroidSwarm>().iter(app.world()).count(), 2);
+    // the next wave is made up by the director and comes later
+    let (in_seconds, wave) = forecast(&mut app);
+    let config = app.world().resource::<AsteroidConfig>();
+    assert!(in_seconds > 0.7 * config.min_wave_interval, "next wave forecast in {in_seconds}s");
+    assert!(wave.swarms >= 1);
+}
+
+#[test]
+fn asteroid_difficulty_grows_with_time_and_score() {
+    let config = AsteroidConfig::default();
+    let score = Score::default();
+    let early = config.difficulty(0.0, &score);
+    let late = config.difficulty(0.5 * config.ramp_duration, &score);
+    assert!(late > early, "difficulty went from {early} to {late}");
+
+    let mut good_score = Score::default();
+    good_score.energy_rate = config.ramp_energy_rate;
+    assert_eq!(config.difficulty(0.0, &good_score), 1.0);
+}
+
+#[test]
+fn escaping_collector_is_lost_to_space() {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<WorldBoundary>().radius = 200.0;
+
+    // far above escape speed, straight away from the sun
+    let escape = escape_speed(gravitational_parameter(&Mass(SUN_MASS)), 150.0);
+    spawn_collector(&mut app, Vec2::new(0.0, -150.0), Vec2::new(0.0, -2.0 * escape));
+    // a collector on a circular orbit beyond the boundary isn't going anywhere and stays
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 250.0);
+    spawn_collector(&mut app, Vec2::new(0.0, 250.0), Vec2::new(speed, 0.0));
+    run_for(&mut app, 5.0);
+
+    let remaining = app.world_mut().query::<&Satellite>().iter(app.world()).count();
+    assert_eq!(remaining, 1, "only the escaping collector should be gone");
+    assert_eq!(app.world().resource::<LostToSpace>().collectors, 1);
+}
+
+#[test]
+fn colliding_collectors_leave_debris_in_orbit_until_it_decays() {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<DebrisConfig>().lifetime = 20.0..30.0;
+
+    // two collectors on the same orbit flying towards each other, they meet after about 10 seconds
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let angle = 2.0 * 10.0 * speed / 100.0;
+    let start = Vec2::from_angle(PI + angle);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    spawn_collector(&mut app, start * 100.0, -start.perp() * speed);
+    run_for(&mut app, 12.0);
+    assert_eq!(app.world().resource::<FatalCollisions>().0.len(), 2, "both collectors should have crashed");
+
+    let pieces = app.world().resource::<DebrisConfig>().pieces;
+    let debris = app
+        .world_mut()
+        .query_filtered::<(&Transform, &OrbitalElements), With<Debris>>()
+        .iter(app.world())
+        .map(|(transform, elements)| (transform.translation.xy(), *elements))
+        .collect::<Vec<_>>();
+    assert_eq!(debris.len(), 2 * pieces);
+    for (position, elements) in debris {
+        // the pieces stay close to the orbit the collectors were on
+        assert!((position.length() - 100.0).abs() < 15.0, "debris at {position}");
+        assert!(elements.bound);
+    }
+
+    run_for(&mut app, 30.0);
+    assert_eq!(app.world_mut().query::<&Debris>().iter(app.world()).count(), 0, "debris should have decayed");
+}
+
+#[test]
+fn debris_damages_collectors_it_hits() {
+    let mut app = headless_app();
+
+    // a piece of debris coming straight at a collector on a circular orbit, they meet after about a second
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let piece = app
+        .world_mut()
+        .spawn((
+            Debris {
+                decay: Timer::from_seconds(60.0, TimerMode::Once),
+                arming: Timer::from_seconds(0.0, TimerMode::Once),
+            },
+            Attractee,
+            Mass(0.1),
+            HitBox {
+                shape: HitBoxShape::Circle { radius: 1.0 },
+                layer: CollisionLayer::Debris,
+                ..default()
+            },
+            Transform::from_translation(Vec3::new(-100.0, -20.0, 0.0)),
+            Velocity(Vec2::new(0.0, speed)),
+        ))
+        .id();
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    let collector = single::<Satellite>(&mut app);
+    run_for(&mut app, 2.0);
+
+    assert!(app.world().get_entity(piece).is_err(), "the debris should be gone");
+    let expected = app.world().resource::<DamageConfig>().impact_damage(2.0 * speed, 1.0, 0.1);
+    let health = *app.world().get::<Health>(collector).unwrap();
+    assert!((health.max - health.current - expected).abs() < 0.1 * expected, "collector lost {} health", health.max - health.current);
+    // and the collector sheds debris of its own
+    let pieces = app.world().resource::<DebrisConfig>().pieces;
+    assert_eq!(app.world_mut().query::<&Debris>().iter(app.world()).count(), pieces / 2);
+}
+
+#[test]
+fn bodies_without_health_survive_damage_on_layers_configured_so() {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<DebrisConfig>().pieces = 0;
+    let mut config = app.world_mut().resource_mut::<DamageConfig>();
+    config.destroyed_without_health = config.destroyed_without_health.without(CollisionLayer::Debris);
+
+    // the same piece of debris coming straight at a collector, they meet after about a second
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let piece = app
+        .world_mut()
+        .spawn((
+            Debris {
+                decay: Timer::from_seconds(60.0, TimerMode::Once),
+                arming: Timer::from_seconds(0.0, TimerMode::Once),
+            },
+            Attractee,
+            Mass(0.1),
+            HitBox {
+                shape: HitBoxShape::Circle { radius: 1.0 },
+                layer: CollisionLayer::Debris,
+                ..default()
+            },
+            Transform::from_translation(Vec3::new(-100.0, -20.0, 0.0)),
+            Velocity(Vec2::new(0.0, speed)),
+        ))
+        .id();
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    let collector = single::<Satellite>(&mut app);
+    run_for(&mut app, 2.0);
+
+    assert!(app.world().get_entity(piece).is_ok(), "the debris should have bounced off");
+    assert!(app.world().get::<Velocity>(piece).unwrap().0.y < 0.0, "the debris should fly back");
+    let health = *app.world().get::<Health>(collector).unwrap();
+    assert!(health.current < health.max, "the collector should still be damaged");
+}
+
+#[test]
+fn sturdier_collector_survives_a_collision_damaged() {
+    let mut app = headless_app();
+
+    // two collectors on the same orbit flying towards each other, they meet after about 10 seconds
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let angle = 2.0 * 10.0 * speed / 100.0;
+    let start = Vec2::from_angle(PI + angle);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    let weak = single::<Satellite>(&mut app);
+    spawn_collector(&mut app, start * 100.0, -start.perp() * speed);
+    let sturdy = app
+        .world_mut()
+        .query_filtered::<Entity, With<Satellite>>()
+        .iter(app.world())
+        .find(|entity| *entity != weak)
+        .unwrap();
+    app.world_mut()
+        .entity_mut(sturdy)
+        .insert((Level { level: 3.0 }, Health::for_level(&Level { level: 3.0 })));
+    run_for(&mut app, 12.0);
+
+    assert!(app.world().get_entity(weak).is_err(), "the level 1 collector should have broken apart");
+    let health = *app.world().get::<Health>(sturdy).expect("the level 3 collector should have survived");
+    let expected = app.world().resource::<DamageConfig>().impact_damage(2.0 * speed, 1.0, 1.0);
+    assert!((health.max - health.current - expected).abs() < 0.1 * expected, "collector lost {} health", health.max - health.current);
+}
+
+/// Which collectors were damaged, once per hit
+#[derive(Resource, Default)]
+struct DamagedCollectors(Vec<Entity>);
+
+#[test]
+fn colliding_collectors_bounce_off_each_other_once() {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<DebrisConfig>().pieces = 0;
+    app.init_resource::<DamagedCollectors>();
+    app.add_observer(|event: On<DamageCollisionEvent>, mut damaged: ResMut<DamagedCollectors>| {
+        damaged.0.push(event.damaged);
+    });
+
+    // two sturdy collectors on the same orbit flying towards each other, they meet after about 10 seconds
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let angle = 2.0 * 10.0 * speed / 100.0;
+    let start = Vec2::from_angle(PI + angle);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    spawn_collector(&mut app, start * 100.0, -start.perp() * speed);
+    let collectors: Vec<Entity> = app
+        .world_mut()
+        .query_filtered::<Entity, With<Satellite>>()
+        .iter(app.world())
+        .collect();
+    for collector in &collectors {
+        app.world_mut()
+            .entity_mut(*collector)
+            .insert((Level { level: 3.0 }, Health::for_level(&Level { level: 3.0 })));
+    }
+    let angular_momentum = |app: &App, collector: Entity| {
+        let position = app.world().get::<Transform>(collector).unwrap().translation.xy();
+        position.perp_dot(app.world().get::<Velocity>(collector).unwrap().0)
+    };
+    let before: Vec<f32> = collectors.iter().map(|collector| angular_momentum(&app, *collector)).collect();
+    run_for(&mut app, 12.0);
+
+    let mut damaged = app.world().resource::<DamagedCollectors>().0.clone();
+    damaged.sort();
+    let mut expected = collectors.clone();
+    expected.sort();
+    assert_eq!(damaged, expected, "each collector should have been damaged exactly once");
+    for (collector, before) in collectors.iter().zip(before) {
+        let after = angular_momentum(&app, *collector);
+        assert!(before * after < 0.0, "collector {collector} should have bounced back, angular momentum {before} -> {after}");
+    }
+    let positions: Vec<Vec2> = collectors
+        .iter()
+        .map(|collector| app.world().get::<Transform>(*collector).unwrap().translation.xy())
+        .collect();
+    assert!(positions[0].distance(positions[1]) > 8.0, "the collectors should have separated");
+}
+
+/// Every contact between two bodies
+#[derive(Resource, Default)]
+struct Contacts(Vec<GeneralCollisionEvent>);
+
+#[test]
+fn collision_rules_decide_what_happens_to_collectors() {
+    let mut app = headless_app();
+    app.world_mut().resource_mut::<CollisionRules>().set(
+        CollisionLayer::Collector,
+        CollisionLayer::Collector,
+        CollisionRule::Bounce,
+    );
+    app.init_resource::<Contacts>();
+    app.add_observer(|event: On<GeneralCollisionEvent>, mut contacts: ResMut<Contacts>| {
+        contacts.0.push(*event);
+    });
+
+    // two collectors on the same orbit flying towards each other, they meet after about 10 seconds
+    let speed = circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 100.0);
+    let angle = 2.0 * 10.0 * speed / 100.0;
+    let start = Vec2::from_angle(PI + angle);
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::new(0.0, -speed));
+    spawn_collector(&mut app, start * 100.0, -start.perp() * speed);
+    run_for(&mut app, 12.0);
+
+    let contacts = &app.world().resource::<Contacts>().0;
+    assert_eq!(contacts.len(), 1, "the collectors should have touched once but got {contacts:?}");
+    assert_eq!(contacts[0].rule, CollisionRule::Bounce);
+    assert!(contacts[0].participants().iter().all(|participant| !participant.destroyed));
+    assert!(app.world().resource::<FatalCollisions>().0.is_empty(), "no collector should have been destroyed");
+    let healths: Vec<Health> = app.world_mut().query::<&Health>().iter(app.world()).copied().collect();
+    assert_eq!(healths.len(), 2);
+    assert!(healths.iter().all(|health| health.current == health.max), "bouncing collectors shouldn't take damage");
+    assert_eq!(app.world_mut().query::<&Debris>().iter(app.world()).count(), 0, "bouncing collectors shouldn't shed debris");
+}
+
+#[test]
+fn fast_collector_crossing_another_within_a_tick_collides() {
+    let mut app = headless_app();
+    app.init_resource::<Contacts>();
+    app.add_observer(|event: On<GeneralCollisionEvent>, mut contacts: ResMut<Contacts>| {
+        contacts.0.push(*event);
+    });
+
+    spawn_collector(&mut app, Vec2::new(-100.0, 0.0), Vec2::ZERO);
+    let slow = single::<Satellite>(&mut app);
+    let position = app.world().get::<Transform>(slow).unwrap().translation.xy();
+    // 40 units per tick, it is 20 units short of the other collector after the first tick and 20 units past it
+    // after the second, so the hitboxes never overlap where they are checked
+    let fast = app
+        .world_mut()
+        .spawn(collector_bundle(position - Vec2::new(60.0, 0.0), Vec2::new(40.0 * TICKS_PER_SECOND as f32, 0.0)))
+        .id();
+    app.update();
+    assert!(app.world().resource::<Contacts>().0.is_empty());
+    app.update();
+
+    let contacts = &app.world().resource::<Contacts>().0;
+    assert_eq!(contacts.len(), 1, "the collectors should have collided once but got {contacts:?}");
+    let mut participants = contacts[0].participants().map(|participant| participant.entity);
+    participants.sort();
+    let mut expected = [slow, fast];
+    expected.sort();
+    assert_eq!(participants, expected);
+}
+
+#[test]
+fn debris_passes_beside_a_collectors_panels() {
+    let mut app = headless_app();
+
+    // a collector with long panels along its orbit and two pieces of debris catching up with it, one aimed at
+    // the panels and one that passes just beside them, far out where the orbits hardly bend over the few seconds
+    spawn_collector(&mut app, Vec2::new(-400.0, 0.0), Vec2::new(0.0, -circular_speed(gravitational_parameter(&Mass(SUN_MASS)), 400.0)));
+    let collector = single::<Satellite>(&mut app);
+    app.world_mut().entity_mut(collector).insert(HitBox {
+        shape: HitBoxShape::ConvexPolygon {
+            vertices: vec![Vec2::new(-1.0, -8.0), Vec2::new(1.0, -8.0), Vec2::new(1.0, 8.0), Vec2::new(-1.0, 8.0)],
+        },
+        layer: CollisionLayer::Collector,
+        ..default()
+    });
+    let position = app.world().get::<Transform>(collector).unwrap().translation.xy();
+    let velocity = app.world().get::<Velocity>(collector).unwrap().0;
+    let mut debris = |offset: Vec2| {
+        app.world_mut()
+            .spawn((
+                Debris {
+                    decay: Timer::from_seconds(60.0, TimerMode::Once),
+                    arming: Timer::from_seconds(0.0, TimerMode::Once),
+                },
+                Attractee,
+                Mass(0.1),
+                HitBox {
+                    shape: HitBoxShape::Circle { radius: 1.0 },
+                    layer: CollisionLayer::Debris,
+                    ..default()
+                },
+                Transform::from_translation((position + offset).extend(0.0)),
+                // moving along with the collector and catching up with it
+                Velocity(velocity + Vec2::new(0.0, -20.0)),
+            ))
+            .id()
+    };
+    let hitting = debris(Vec2::new(0.0, 30.0));
+    let passing = debris(Vec2::new(3.0, 30.0));
+    run_for(&mut app, 2.0);
+
+    assert!(app.world().get_entity(hitting).is_err(), "the debris aimed at the collector should have hit it");
+    assert!(app.world().get_entity(passing).is_ok(), "the debris beside the panels should have passed");
+}